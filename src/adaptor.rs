@@ -0,0 +1,115 @@
+//! Schnorr adaptor signatures i.e. signatures encrypted to a point so that they can only be
+//! completed with its discrete log.
+//!
+//! Alice encrypts her signature on the transaction for each outcome to the corresponding outcome
+//! image she passes to Bob. When the oracles attest Bob recovers the outcome secret from
+//! [`Bob2::receive_oracle_attestation`](crate::bob::Bob2::receive_oracle_attestation) and
+//! decrypts Alice's signature with it. Alice can then recover the secret from the completed
+//! signature.
+use crate::{common::schnorr_challenge, schnorr::Signature, G};
+use curve25519_dalek::{ristretto::RistrettoPoint as Point, scalar::Scalar};
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EncryptedSignature {
+    /// The nonce of the signature before the encryption key is added to it.
+    pub R_hat: Point,
+    pub s_hat: Scalar,
+}
+
+pub fn encrypted_sign(
+    sk: &Scalar,
+    encryption_key: &Point,
+    message: &[u8],
+    rng: &mut (impl RngCore + CryptoRng),
+) -> EncryptedSignature {
+    let r = Scalar::random(rng);
+    let R_hat = &r * &*G;
+    let pk = sk * &*G;
+    // the decrypted signature's nonce
+    let R = R_hat + encryption_key;
+    let challenge = schnorr_challenge(&R, &pk, message);
+    EncryptedSignature {
+        R_hat,
+        s_hat: r + challenge * sk,
+    }
+}
+
+/// Checks that decrypting the signature with the discrete log of `encryption_key` gives a valid
+/// signature on `message` under `pk`.
+pub fn verify_encrypted_signature(
+    pk: &Point,
+    encryption_key: &Point,
+    message: &[u8],
+    encrypted_signature: &EncryptedSignature,
+) -> bool {
+    let R = encrypted_signature.R_hat + encryption_key;
+    let challenge = schnorr_challenge(&R, pk, message);
+    &encrypted_signature.s_hat * &*G == encrypted_signature.R_hat + challenge * pk
+}
+
+pub fn decrypt_signature(
+    decryption_key: &Scalar,
+    encrypted_signature: &EncryptedSignature,
+) -> Signature {
+    Signature {
+        R: encrypted_signature.R_hat + decryption_key * &*G,
+        s: encrypted_signature.s_hat + decryption_key,
+    }
+}
+
+/// Recovers the decryption key from a signature that was decrypted from `encrypted_signature`.
+/// Returns `None` if the signature wasn't decrypted from it with the discrete log of
+/// `encryption_key`.
+pub fn recover_decryption_key(
+    encryption_key: &Point,
+    signature: &Signature,
+    encrypted_signature: &EncryptedSignature,
+) -> Option<Scalar> {
+    let decryption_key = signature.s - encrypted_signature.s_hat;
+    if &decryption_key * &*G == *encryption_key
+        && signature.R == encrypted_signature.R_hat + encryption_key
+    {
+        Some(decryption_key)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::schnorr;
+
+    #[test]
+    fn encrypt_decrypt_recover() {
+        let sk = Scalar::random(&mut rand::thread_rng());
+        let pk = &sk * &*G;
+        let decryption_key = Scalar::random(&mut rand::thread_rng());
+        let encryption_key = &decryption_key * &*G;
+        let message = b"pay alice 3 pay bob 7";
+
+        let encrypted_signature =
+            encrypted_sign(&sk, &encryption_key, message, &mut rand::thread_rng());
+        assert!(verify_encrypted_signature(
+            &pk,
+            &encryption_key,
+            message,
+            &encrypted_signature
+        ));
+        assert!(!verify_encrypted_signature(
+            &pk,
+            &(encryption_key + &Scalar::one() * &*G),
+            message,
+            &encrypted_signature
+        ));
+
+        let signature = decrypt_signature(&decryption_key, &encrypted_signature);
+        assert!(schnorr::verify(&pk, message, &signature));
+        assert_eq!(
+            recover_decryption_key(&encryption_key, &signature, &encrypted_signature),
+            Some(decryption_key)
+        );
+    }
+}
@@ -1,28 +1,34 @@
-use crate::common::Params;
+use crate::bob::Bob1;
+use crate::common::{derive_elgamal_base, OutcomeEncoding, Params};
+use crate::error::{Error, Result};
 use crate::messages::*;
 use crate::G;
-use anyhow::anyhow;
 use curve25519_dalek::{ristretto::RistrettoPoint as Point, scalar::Scalar};
-use zkp::{toolbox::prover::Prover, Transcript};
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use zkp::toolbox::prover::Prover;
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Alice1 {
     secrets: Vec<(Scalar, Scalar, Point)>,
     commits: Vec<Commit>,
+    params_hash: [u8; 32],
 }
 
 impl Alice1 {
-    pub fn new(params: &Params) -> (Alice1, Message1) {
+    pub fn new(params: &Params, rng: &mut (impl RngCore + CryptoRng)) -> (Alice1, Message1) {
+        let elgamal_base = derive_elgamal_base();
         let (commits, secrets) = (0..params.M())
             .map(|_| {
                 let (padi, ri, ri_mapped) = {
-                    let ri = Scalar::random(&mut rand::thread_rng());
-                    let (ri_mapped, padi) = crate::common::map_Zq_to_G(&ri);
+                    let ri = Scalar::random(rng);
+                    let (ri_mapped, padi) = crate::common::map_Zq_to_G(&ri, rng);
                     (padi, ri, ri_mapped)
                 };
 
                 let Ri = &ri * &*G;
-                let ri_prime = Scalar::random(&mut rand::thread_rng());
-                let C_i = (&ri_prime * &*G, ri_prime * params.elgamal_base + ri_mapped);
+                let ri_prime = Scalar::random(rng);
+                let C_i = (&ri_prime * &*G, ri_prime * elgamal_base + ri_mapped);
 
                 (
                     Commit {
@@ -43,37 +49,52 @@ impl Alice1 {
             Alice1 {
                 secrets,
                 commits: commits.clone(),
+                params_hash: params.hash(),
             },
             message,
         )
     }
 
-    pub fn receive_message(
+    /// Produces `Message3` without a round trip by answering the Fiat-Shamir challenge derived
+    /// from our own `Message1` (see [`Bob1::gen_message2_non_interactive`]). The params must be
+    /// built for the non-interactive protocol.
+    pub fn prove_non_interactive(
         self,
-        message: Message2,
         secret_sigs: Vec<Scalar>,
         params: &Params,
-    ) -> anyhow::Result<Message3> {
-        let NB = params.NB();
-        if let Some(bad_index) = message.bucket_mapping.iter().find(|map| **map >= NB) {
-            return Err(anyhow!(
-                "bucket was mapped to {} which is outside of range 0..{}",
-                bad_index,
-                NB
-            ));
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Result<Message3> {
+        params.check_hash(&self.params_hash)?;
+        if !params.non_interactive {
+            return Err(Error::InteractiveParams);
         }
+        let message2 = Bob1::gen_message2_non_interactive(&self.commits, params);
+        self.receive_message(message2, secret_sigs, params, rng)
+    }
 
-        if message.openings.len() != params.num_openings() {
-            return Err(anyhow!(
-                "wrong number of openings requested. Expected {} got {}",
-                params.num_openings(),
-                message.openings.len()
-            ));
+    pub fn receive_message(
+        self,
+        message: Message2,
+        secret_sigs: Vec<Scalar>,
+        params: &Params,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Result<Message3> {
+        params.check_hash(&self.params_hash)?;
+        params.validate()?;
+        message.validate(params)?;
+        if secret_sigs.len() != params.n_secrets() {
+            return Err(Error::WrongNumberOfSecrets {
+                expected: params.n_secrets(),
+                got: secret_sigs.len(),
+            });
         }
 
+        let elgamal_base = derive_elgamal_base();
+        let mut transcript = crate::dleq::transcript(params, &self.commits, &message);
         let Alice1 {
             mut secrets,
             mut commits,
+            ..
         } = self;
 
         let mut i = 0;
@@ -105,61 +126,69 @@ impl Alice1 {
             .map(|oracle_index| params.iter_anticipations(oracle_index).collect::<Vec<_>>())
             .collect::<Vec<_>>();
 
-        let scalar_polys = (0..params.n_outcomes)
-            .map(|outcome_index| {
-                let secret_sig = secret_sigs[outcome_index as usize].clone();
-                let mut poly = crate::poly::ScalarPoly::random(
-                    (params.threshold - 1) as usize,
-                    &mut rand::thread_rng(),
-                );
+        let scalar_polys = secret_sigs
+            .into_iter()
+            .map(|secret_sig| {
+                let mut poly =
+                    crate::poly::ScalarPoly::random((params.threshold - 1) as usize, rng);
                 poly.push_front(secret_sig);
                 poly
             })
             .collect::<Vec<_>>();
 
-        let bit_map: Vec<Vec<[Scalar; 2]>> = (0..n_oracles)
-            .map(|_| {
-                (0..params.n_outcome_bits())
-                    .map(|_| {
-                        [
-                            Scalar::random(&mut rand::thread_rng()),
-                            Scalar::random(&mut rand::thread_rng()),
-                        ]
-                    })
-                    .collect()
-            })
-            .collect();
+        let n_digits = params.n_digits();
+        let base = params.digit_base();
+        // a random scalar for every value of every digit of every oracle
+        let bit_map: Vec<Vec<Vec<Scalar>>> = match params.outcome_encoding {
+            OutcomeEncoding::Digits => (0..n_oracles)
+                .map(|_| {
+                    params
+                        .n_digit_values()
+                        .iter()
+                        .map(|n_values| (0..*n_values).map(|_| Scalar::random(rng)).collect())
+                        .collect()
+                })
+                .collect(),
+            OutcomeEncoding::Direct => vec![],
+        };
 
         let mut encryptions = vec![];
 
-        let mut transcript = Transcript::new(b"dlc-dleqs");
         let mut prover = Prover::new(b"dlc-dleqs", &mut transcript);
 
-        for (oracle_index, bits_window) in buckets
-            .chunks((params.n_outcome_bits() * 2 * params.bucket_size as u32) as usize)
-            .enumerate()
-        {
-            for (outcome_bit_index, bit_window) in bits_window
-                .chunks((2 * params.bucket_size) as usize)
-                .enumerate()
+        let mut bucket_windows = buckets.chunks(params.bucket_size as usize);
+        for (oracle_index, oracle_anticipations) in anticipated_attestations.iter().enumerate() {
+            for (outcome_digit_index, digit_anticipations) in
+                oracle_anticipations.iter().enumerate()
             {
-                for (bit_value_index, bit_value_window) in
-                    bit_window.chunks(params.bucket_size as usize).enumerate()
+                for (digit_value_index, anticipated_attestation) in
+                    digit_anticipations.iter().enumerate()
                 {
-                    let t = &bit_map[oracle_index][outcome_bit_index][bit_value_index];
-                    let anticipated_attestation =
-                        anticipated_attestations[oracle_index][outcome_bit_index][bit_value_index];
-
-                    for (commit, (ri, ri_prime, ri_mapped)) in bit_value_window {
+                    let digit_value_window = bucket_windows
+                        .next()
+                        .expect("there is a bucket for every digit value");
+                    let t = match params.outcome_encoding {
+                        OutcomeEncoding::Digits => {
+                            bit_map[oracle_index][outcome_digit_index][digit_value_index]
+                        }
+                        // the outcome is the digit value and its share is encrypted directly
+                        OutcomeEncoding::Direct => {
+                            let secret_index = params
+                                .secret_index(digit_value_index as u32)
+                                .expect("there is a digit value for each outcome");
+                            scalar_polys[secret_index].eval((oracle_index + 1) as u32)
+                        }
+                    };
+                    for (commit, (ri, ri_prime, ri_mapped)) in digit_value_window {
                         // compute the ElGamal encryption of ri_mapped
                         let ri_encryption = anticipated_attestation * ri_prime + ri_mapped;
                         // create proof ElGamal encryption value is same as commitment
                         crate::dleq::prove_eqaulity(
                             &mut prover,
-                            ri_prime.clone(),
+                            *ri_prime,
                             ri_encryption,
-                            anticipated_attestation,
-                            params.elgamal_base,
+                            *anticipated_attestation,
+                            elgamal_base,
                             commit.C,
                         );
 
@@ -173,16 +202,22 @@ impl Alice1 {
 
         let proof = prover.prove_batchable();
 
-        let secret_share_pads_by_oracle = (0..n_oracles)
+        let secret_prefixes = match params.outcome_encoding {
+            OutcomeEncoding::Digits => params.secret_prefixes(),
+            OutcomeEncoding::Direct => vec![],
+        };
+        let secret_share_pads_by_oracle = (0..bit_map.len())
             .map(|oracle_index| {
-                let secret_share_pads = compute_pads(&bit_map[oracle_index][..]);
-
-                secret_share_pads
-                    .into_iter()
-                    .enumerate()
-                    .take(params.n_outcomes as usize)
-                    .map(|(outcome_index, pad)| {
-                        let scalar_poly = &scalar_polys[outcome_index];
+                secret_prefixes
+                    .iter()
+                    .map(|(secret_index, prefix)| {
+                        let pad = prefix
+                            .digits(base, n_digits)
+                            .map(|(digit_index, digit_value)| {
+                                bit_map[oracle_index][digit_index][digit_value as usize]
+                            })
+                            .sum::<Scalar>();
+                        let scalar_poly = &scalar_polys[*secret_index];
                         let secret_share = scalar_poly.eval((oracle_index + 1) as u32);
                         pad + secret_share
                     })
@@ -192,10 +227,10 @@ impl Alice1 {
 
         let bit_map_images = bit_map
             .iter()
-            .map(|oracle_bits| {
-                oracle_bits
+            .map(|oracle_digits| {
+                oracle_digits
                     .iter()
-                    .map(|oracle_bit| [&oracle_bit[0] * &*G, &oracle_bit[1] * &*G])
+                    .map(|oracle_digit| oracle_digit.iter().map(|t| t * &*G).collect())
                     .collect()
             })
             .collect();
@@ -219,19 +254,51 @@ impl Alice1 {
     }
 }
 
-fn compute_pads(pads: &[[Scalar; 2]]) -> Vec<Scalar> {
-    _compute_pads(pads.len() - 1, Scalar::zero(), pads)
-}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        codec,
+        common::AttestationScheme,
+        oracle::{Oracle, OutcomeDescriptor},
+    };
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    #[test]
+    fn seeded_rng_gives_reproducible_commitments() {
+        let mut rng = ChaCha20Rng::from_seed([1u8; 32]);
+        let outcome_descriptor = OutcomeDescriptor {
+            n_outcomes: 4,
+            base: 2,
+            attestation_scheme: AttestationScheme::Linear,
+        };
+        let announcement = Oracle::random(&mut rng)
+            .announce("event".into(), outcome_descriptor, 0, &mut rng)
+            .unwrap();
+        let params = Params {
+            event_id: "event".into(),
+            oracle_keys: vec![(announcement.public_key, announcement.event.nonces)],
+            closed_proportion: 0.5,
+            bucket_size: 2,
+            threshold: 1,
+            n_outcomes: 4,
+            base: 2,
+            attestation_scheme: AttestationScheme::Linear,
+            outcome_encoding: OutcomeEncoding::Digits,
+            security_param: 2,
+            non_interactive: false,
+            outcome_intervals: None,
+        };
 
-fn _compute_pads(cur_bit: usize, acc: Scalar, pads: &[[Scalar; 2]]) -> Vec<Scalar> {
-    let zero = acc + { &pads[cur_bit][0] };
-    let one = acc + { &pads[cur_bit][1] };
+        let (alice, m1_a) = Alice1::new(&params, &mut ChaCha20Rng::from_seed([2u8; 32]));
+        let (_, m1_b) = Alice1::new(&params, &mut ChaCha20Rng::from_seed([2u8; 32]));
+        assert_eq!(codec::encode(&m1_a), codec::encode(&m1_b));
 
-    if cur_bit == 0 {
-        vec![zero, one]
-    } else {
-        let mut children = _compute_pads(cur_bit - 1, zero, pads);
-        children.extend(_compute_pads(cur_bit - 1, one, pads));
-        children
+        // the params were built for the interactive protocol
+        assert!(matches!(
+            alice.prove_non_interactive(vec![Scalar::zero(); 4], &params, &mut rng),
+            Err(Error::InteractiveParams)
+        ));
     }
 }
@@ -1,116 +1,610 @@
-use clap::Parser;
-use curve25519_dalek::{ristretto::RistrettoPoint as Point, scalar::Scalar};
+use anyhow::Context;
+use clap::{Args, Parser, Subcommand};
+use curve25519_dalek::scalar::Scalar;
 use dlc_venc_adaptor::{
     alice::*,
     bob::*,
-    common::{compute_optimal_params, Params},
-    oracle::Oracle,
+    codec::{self, WireMessage},
+    common::{compute_optimal_params, AttestationScheme, OutcomeEncoding, Params},
+    contract::{CetSignatures, Contract, Payout},
+    error::Error,
+    oracle::{verify_attestations, Oracle, OracleAnnouncement, OutcomeDescriptor},
+    schnorr,
+    session::{AliceSession, AliceState, BobSession, BobState, OutgoingMessage},
+    transport::{read_frame, write_frame, OracleRequest},
     G,
 };
 use rand::Rng;
-use serde::Serialize;
-use std::time::Instant;
+#[cfg(unix)]
+use std::os::unix::{
+    fs::FileTypeExt,
+    net::{UnixListener, UnixStream},
+};
+use std::{
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    time::Instant,
+};
 
 #[derive(Parser, Debug)]
-#[clap(author, version, about, long_about = None)]
+#[clap(author, version, about, long_about = None, subcommand_negates_reqs = true)]
 struct CliArgs {
     /// The security parameter (how many bits of security for the overall protocol)
     #[clap(short, default_value_t = 30)]
     s: u8,
     /// The number of outcomes
+    #[clap(long, required = true)]
+    n_outcomes: Option<u32>,
+    /// The number of oracles
+    #[clap(long, required = true)]
+    n_oracles: Option<u16>,
+    /// The threshold of oracles that is required to attest
+    #[clap(long, required = true)]
+    threshold: Option<u16>,
+    /// Derive Bob's challenge via Fiat-Shamir so Alice sends a single offer
+    #[clap(long)]
+    non_interactive: bool,
+    /// Alice's payout steps up over this many equally sized intervals of outcomes with a secret
+    /// for each (rather than going up with every outcome)
+    #[clap(long)]
+    n_intervals: Option<u32>,
+    /// The base the oracles attest to the outcome in (one nonce per digit)
+    #[clap(long, default_value_t = 2)]
+    base: u32,
+    /// The oracles attest with a Schnorr signature on each digit rather than `(d+1)*r + x`
+    #[clap(long)]
+    schnorr: bool,
+    /// Encrypt the secret shares directly to each outcome with a single nonce per oracle rather
+    /// than to the digits of the outcome
+    #[clap(long)]
+    direct: bool,
+    /// Run with both the digit and the direct outcome encodings to compare them
+    #[clap(long)]
+    compare_encodings: bool,
+    /// Run one of the parties in its own process instead of everything in this one
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Serve announcements and attestations from a set of oracles
+    Oracle(OracleArgs),
+    /// Encrypt Alice's secrets to Bob and give him her signatures on the CETs
+    Alice(PartyArgs),
+    /// Verify Alice's encryptions then decrypt her signature on the CET for the attested outcome
+    Bob(PartyArgs),
+}
+
+#[derive(Args, Debug)]
+struct OracleArgs {
+    /// Where to listen for requests
+    #[clap(long)]
+    listen: String,
+    /// The event the oracles announce
+    #[clap(long, default_value = "demo")]
+    event_id: String,
+    /// The number of outcomes
     #[clap(long)]
     n_outcomes: u32,
     /// The number of oracles
     #[clap(long)]
     n_oracles: u16,
+    /// The base the oracles attest to the outcome in (one nonce per digit)
+    #[clap(long, default_value_t = 2)]
+    base: u32,
+    /// The oracles attest with a Schnorr signature on each digit rather than `(d+1)*r + x`
+    #[clap(long)]
+    schnorr: bool,
+    /// The outcome to attest to (random if not given)
+    #[clap(long)]
+    outcome: Option<u32>,
+}
+
+#[derive(Args, Debug)]
+struct PartyArgs {
+    /// The address of the oracle server
+    #[clap(long)]
+    oracle: String,
+    /// The address Bob listens on for Alice
+    #[clap(long)]
+    bob: String,
+    /// The event the contract is on
+    #[clap(long, default_value = "demo")]
+    event_id: String,
+    /// The security parameter (how many bits of security for the overall protocol)
+    #[clap(short, default_value_t = 30)]
+    s: u8,
     /// The threshold of oracles that is required to attest
     #[clap(long)]
     threshold: u16,
+    /// Derive Bob's challenge via Fiat-Shamir so Alice sends a single offer
+    #[clap(long)]
+    non_interactive: bool,
+    /// Alice's payout steps up over this many equally sized intervals of outcomes
+    #[clap(long)]
+    n_intervals: Option<u32>,
 }
 
 fn main() -> anyhow::Result<()> {
     let args = CliArgs::parse();
-    let elgamal_base = Point::random(&mut rand::thread_rng());
+    match &args.command {
+        Some(Command::Oracle(oracle_args)) => return serve_oracles(oracle_args),
+        Some(Command::Alice(party_args)) => return run_alice(party_args),
+        Some(Command::Bob(party_args)) => return run_bob(party_args),
+        None => {}
+    }
+    let outcome_encodings = if args.compare_encodings {
+        vec![OutcomeEncoding::Digits, OutcomeEncoding::Direct]
+    } else if args.direct {
+        vec![OutcomeEncoding::Direct]
+    } else {
+        vec![OutcomeEncoding::Digits]
+    };
 
-    let oracles = (0..args.n_oracles)
-        .map(|_| Oracle::random(args.n_outcomes, &mut rand::thread_rng()))
-        .collect::<Vec<_>>();
+    for outcome_encoding in outcome_encodings {
+        println!("Outcome encoding: {:?}", outcome_encoding);
+        run(&args, outcome_encoding)?;
+    }
 
-    let (closed_proportion, bucket_size) =
-        compute_optimal_params(args.s, args.n_outcomes as u32, args.n_oracles as u32);
-    let params = Params {
-        oracle_keys: oracles
-            .iter()
-            .map(|oracle| (oracle.public_key(), oracle.public_nonce().to_vec()))
-            .collect(),
-        n_outcomes: args.n_outcomes,
-        bucket_size,
-        closed_proportion,
-        elgamal_base,
-        threshold: args.threshold,
+    Ok(())
+}
+
+fn run(args: &CliArgs, outcome_encoding: OutcomeEncoding) -> anyhow::Result<()> {
+    // clap makes sure these are given when there's no subcommand
+    let n_outcomes = args.n_outcomes.expect("n_outcomes is required");
+    let n_oracles = args.n_oracles.expect("n_oracles is required");
+    let threshold = args.threshold.expect("threshold is required");
+
+    let digit_base = match outcome_encoding {
+        OutcomeEncoding::Digits => args.base,
+        OutcomeEncoding::Direct => n_outcomes.max(2),
+    };
+
+    let attestation_scheme = if args.schnorr {
+        AttestationScheme::Schnorr
+    } else {
+        AttestationScheme::Linear
+    };
+
+    let outcome_descriptor = OutcomeDescriptor {
+        n_outcomes,
+        base: digit_base,
+        attestation_scheme,
     };
 
-    let secret_sigs = (0..params.n_outcomes)
-        .map(|_| Scalar::random(&mut rand::thread_rng()))
+    let mut oracles = (0..n_oracles)
+        .map(|_| Oracle::random(&mut rand::thread_rng()))
         .collect::<Vec<_>>();
-    let anticipated_sigs = secret_sigs.iter().map(|s| s * &*G).collect();
 
-    println!("Params s: {} n_oracles: {} threshold: {} n_encryptions: {} bucket_size: {} proportion_closed: {}", args.s, args.n_oracles, args.threshold, params.M(), params.bucket_size, params.closed_proportion);
+    let announcements = oracles
+        .iter_mut()
+        .map(|oracle| oracle.announce("run".into(), outcome_descriptor, 0, &mut rand::thread_rng()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let contract = payout_contract(n_outcomes, args.n_intervals);
+    let params = Params::from_announcements(
+        &announcements,
+        threshold,
+        args.s,
+        outcome_encoding,
+        args.non_interactive,
+        Some(contract.outcome_intervals()),
+    )?;
+
+    if outcome_encoding == OutcomeEncoding::Digits && args.base != 2 {
+        let (closed_proportion, bucket_size) = compute_optimal_params(
+            args.s,
+            args.non_interactive,
+            n_outcomes,
+            n_oracles as u32,
+            2,
+        );
+        let binary_params = Params {
+            base: 2,
+            closed_proportion,
+            bucket_size,
+            ..params.clone()
+        };
+        println!(
+            "Base {} n_digits: {} n_encryptions: {} vs binary n_digits: {} n_encryptions: {}",
+            args.base,
+            params.n_digits(),
+            params.M(),
+            binary_params.n_digits(),
+            binary_params.M()
+        );
+    }
+
+    let (secret_sigs, anticipated_sigs) = contract.gen_secrets(&mut rand::thread_rng());
+
+    // Alice's signature on the CET for each secret encrypted to the secret's image
+    let alice_sk = Scalar::random(&mut rand::thread_rng());
+    let alice_pk = &alice_sk * &*G;
+    let encrypted_signatures =
+        contract.sign_cets(&alice_sk, &anticipated_sigs, &mut rand::thread_rng())?;
+    contract.verify_cet_signatures(&alice_pk, &anticipated_sigs, &encrypted_signatures)?;
+
+    println!("Params s: {} n_oracles: {} threshold: {} n_encryptions: {} bucket_size: {} proportion_closed: {} n_secrets: {} n_pads: {}", args.s, n_oracles, threshold, params.M(), params.bucket_size, params.closed_proportion, params.n_secrets(), params.secret_prefixes().len());
     let start_round1 = Instant::now();
-    let (alice, m1) = Alice1::new(&params);
+    let (alice, m1) = Alice1::new(&params, &mut rand::thread_rng());
     let m1_encode_len = encode_len(&m1);
     println!(
         "End round 1 elapsed: {:?} transmitted: {}",
         start_round1.elapsed(),
         m1_encode_len
     );
-    let start_round2 = Instant::now();
-    let (bob, m2) = Bob1::new(m1, &params)?;
-    let m2_encode_len = encode_len(&m2);
+
+    let bob = if args.non_interactive {
+        let start_round3 = Instant::now();
+        let m3 = alice.prove_non_interactive(secret_sigs, &params, &mut rand::thread_rng())?;
+        let m3_encode_len = encode_len(&m3);
+        println!(
+            "End non-interactive proof elapsed: {:?} transmitted: {}",
+            start_round3.elapsed(),
+            m3_encode_len
+        );
+        let start_round4 = Instant::now();
+        let bob = Bob1::new_non_interactive(m1, &params)?;
+        let bob = bob.receive_message(m3, anticipated_sigs.clone(), &params)?;
+        println!("End verification elapsed: {:?}", start_round4.elapsed());
+        println!(
+            "Total elapsed: {:?} transmitted: {}",
+            start_round1.elapsed(),
+            m1_encode_len + m3_encode_len
+        );
+        bob
+    } else {
+        let start_round2 = Instant::now();
+        let (bob, m2) = Bob1::new(m1, &params, &mut rand::thread_rng())?;
+        let m2_encode_len = encode_len(&m2);
+        println!(
+            "End round 2 elapsed: {:?} transmitted: {}",
+            start_round2.elapsed(),
+            m2_encode_len
+        );
+        let start_round3 = Instant::now();
+        let m3 = alice.receive_message(m2, secret_sigs, &params, &mut rand::thread_rng())?;
+        let m3_encode_len = encode_len(&m3);
+        println!(
+            "End round 3 elapsed: {:?} transmitted: {}",
+            start_round3.elapsed(),
+            m3_encode_len
+        );
+        let start_round4 = Instant::now();
+        let bob = bob.receive_message(m3, anticipated_sigs.clone(), &params)?;
+        println!("End round 4 elapsed: {:?}", start_round4.elapsed());
+
+        let total_transmit_interactive = m1_encode_len + m2_encode_len + m3_encode_len;
+        let total_transmit_non_interactive = m1_encode_len + m3_encode_len;
+
+        println!(
+            "Total elapsed: {:?} sans-preprocessing: {:?} transmitted: {} non-interactive: {}",
+            start_round1.elapsed(),
+            start_round2.elapsed(),
+            total_transmit_interactive,
+            total_transmit_non_interactive
+        );
+        bob
+    };
+
+    let outcome_index = rand::thread_rng().gen_range(0..n_outcomes);
+
+    let attestations = oracles
+        .iter_mut()
+        .map(|oracle| oracle.attest("run", outcome_index))
+        .collect::<Result<Vec<_>, _>>()?;
+    verify_attestations(&attestations, &params)?;
+    println!("got attestation");
+    let recovery = bob.receive_oracle_attestation(outcome_index, &attestations, &params)?;
+
     println!(
-        "End round 2 elapsed: {:?} transmitted: {}",
-        start_round2.elapsed(),
-        m2_encode_len
+        "got the secret sig for secret {}: {:?}",
+        recovery.secret_index, recovery.secret
+    );
+    let (payout, signature) = contract.complete_cet(&recovery, &encrypted_signatures)?;
+    anyhow::ensure!(
+        schnorr::verify(
+            &alice_pk,
+            &contract.cet_message(recovery.secret_index),
+            &signature
+        ),
+        "the decrypted signature is invalid"
+    );
+    anyhow::ensure!(
+        contract.payout(outcome_index) == Some(payout),
+        "completed the CET for the wrong payout"
     );
-    let start_round3 = Instant::now();
-    let m3 = alice.receive_message(m2, secret_sigs, &params)?;
-    let m3_encode_len = encode_len(&m3);
     println!(
-        "End round 3 elapsed: {:?} transmitted: {}",
-        start_round3.elapsed(),
-        m3_encode_len
+        "decrypted Alice's signature on the CET for outcome {} paying Alice {} and Bob {}",
+        outcome_index, payout.alice, payout.bob
     );
-    let start_round4 = Instant::now();
-    let bob = bob.receive_message(m3, anticipated_sigs, &params)?;
-    println!("End round 4 elapsed: {:?}", start_round4.elapsed());
 
-    let total_transmit_interactive = m1_encode_len + m2_encode_len + m3_encode_len;
-    let total_transmit_non_interactive = m1_encode_len + m3_encode_len;
+    Ok(())
+}
+
+/// Alice's payout goes up with the outcome over `n_intervals` steps (or with every outcome) and Bob
+/// gets the rest of the collateral.
+fn payout_contract(n_outcomes: u32, n_intervals: Option<u32>) -> Contract {
+    let n_steps = n_intervals.unwrap_or(n_outcomes) as u64;
+    Contract::from_payout_function(n_outcomes, |outcome_index| {
+        let alice = outcome_index as u64 * n_steps / n_outcomes as u64;
+        Payout {
+            alice,
+            bob: n_steps - alice,
+        }
+    })
+}
 
+fn encode_len(message: &impl WireMessage) -> usize {
+    codec::encode(message).len()
+}
+
+trait Stream: Read + Write {}
+impl<T: Read + Write> Stream for T {}
+
+/// Addresses containing a `/` are the paths of Unix sockets and anything else is a TCP address.
+fn is_unix_socket(addr: &str) -> bool {
+    addr.contains('/')
+}
+
+#[cfg(not(unix))]
+fn no_unix_sockets(addr: &str) -> anyhow::Error {
+    anyhow::anyhow!(
+        "{} is a Unix socket which isn't supported on this platform",
+        addr
+    )
+}
+
+fn connect(addr: &str) -> anyhow::Result<Box<dyn Stream>> {
+    if is_unix_socket(addr) {
+        #[cfg(unix)]
+        return Ok(Box::new(UnixStream::connect(addr)?));
+        #[cfg(not(unix))]
+        return Err(no_unix_sockets(addr));
+    }
+    Ok(Box::new(TcpStream::connect(addr)?))
+}
+
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+impl Listener {
+    fn bind(addr: &str) -> anyhow::Result<Listener> {
+        if is_unix_socket(addr) {
+            #[cfg(unix)]
+            {
+                // a socket left behind by an earlier run would stop us binding
+                if let Ok(metadata) = std::fs::metadata(addr) {
+                    anyhow::ensure!(metadata.file_type().is_socket(), "{} isn't a socket", addr);
+                    std::fs::remove_file(addr)?;
+                }
+                return Ok(Listener::Unix(UnixListener::bind(addr)?));
+            }
+            #[cfg(not(unix))]
+            return Err(no_unix_sockets(addr));
+        }
+        Ok(Listener::Tcp(TcpListener::bind(addr)?))
+    }
+
+    fn accept(&self) -> anyhow::Result<Box<dyn Stream>> {
+        let stream: Box<dyn Stream> = match self {
+            Listener::Tcp(listener) => Box::new(listener.accept()?.0),
+            #[cfg(unix)]
+            Listener::Unix(listener) => Box::new(listener.accept()?.0),
+        };
+        Ok(stream)
+    }
+}
+
+fn send(stream: &mut impl Write, message: &impl WireMessage) -> anyhow::Result<()> {
+    write_frame(stream, &codec::encode(message))?;
+    Ok(())
+}
+
+fn send_all(stream: &mut impl Write, outgoing: Vec<OutgoingMessage>) -> anyhow::Result<()> {
+    for message in outgoing {
+        write_frame(stream, &message.bytes)?;
+    }
+    Ok(())
+}
+
+fn receive(stream: &mut impl Read) -> anyhow::Result<Vec<u8>> {
+    read_frame(stream)?.context("the connection was closed")
+}
+
+/// Serves requests until killed. The oracles attest to the event as soon as anyone asks for the
+/// attestations.
+fn serve_oracles(args: &OracleArgs) -> anyhow::Result<()> {
+    let outcome_descriptor = OutcomeDescriptor {
+        n_outcomes: args.n_outcomes,
+        base: args.base,
+        attestation_scheme: if args.schnorr {
+            AttestationScheme::Schnorr
+        } else {
+            AttestationScheme::Linear
+        },
+    };
+    let mut oracles = (0..args.n_oracles)
+        .map(|_| Oracle::random(&mut rand::thread_rng()))
+        .collect::<Vec<_>>();
+    let announcements = oracles
+        .iter_mut()
+        .map(|oracle| {
+            oracle.announce(
+                args.event_id.clone(),
+                outcome_descriptor,
+                0,
+                &mut rand::thread_rng(),
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let outcome_index = match args.outcome {
+        Some(outcome_index) => outcome_index,
+        None => rand::thread_rng().gen_range(0..args.n_outcomes),
+    };
+
+    let listener = Listener::bind(&args.listen)?;
     println!(
-        "Total elapsed: {:?} sans-preprocessing: {:?} transmitted: {} non-interactive: {}",
-        start_round1.elapsed(),
-        start_round2.elapsed(),
-        total_transmit_interactive,
-        total_transmit_non_interactive
+        "serving {} oracles for event {} on {}",
+        args.n_oracles, args.event_id, args.listen
     );
+    loop {
+        let mut stream = listener.accept()?;
+        let mut handle_request = || -> anyhow::Result<()> {
+            match codec::decode::<OracleRequest>(&receive(&mut stream)?)? {
+                OracleRequest::Announcements { event_id } => {
+                    if event_id != args.event_id {
+                        return Err(Error::UnknownEvent { event_id }.into());
+                    }
+                    for announcement in &announcements {
+                        send(&mut stream, announcement)?;
+                    }
+                    println!("sent announcements");
+                }
+                OracleRequest::Attestations { event_id } => {
+                    for oracle in &mut oracles {
+                        send(&mut stream, &oracle.attest(&event_id, outcome_index)?)?;
+                    }
+                    println!("sent attestations to outcome {}", outcome_index);
+                }
+            }
+            Ok(())
+        };
+        if let Err(e) = handle_request() {
+            println!("failed to handle request: {}", e);
+        }
+    }
+}
 
-    let outcome_index = rand::thread_rng().gen_range(0..args.n_outcomes);
-
-    let attestations = oracles
-        .iter()
-        .map(|oracle| oracle.attest(outcome_index))
-        .collect();
-    println!("got attestation");
-    let scalar = bob.receive_oracle_attestation(outcome_index, attestations, &params)?;
+/// Gets the announcements from the oracle server and sets up the contract on the event the same
+/// way as Alice and Bob.
+fn setup_contract(args: &PartyArgs) -> anyhow::Result<(Params, Contract)> {
+    let mut stream = connect(&args.oracle)?;
+    send(
+        &mut stream,
+        &OracleRequest::Announcements {
+            event_id: args.event_id.clone(),
+        },
+    )?;
+    let mut announcements = vec![];
+    while let Some(bytes) = read_frame(&mut stream)? {
+        let announcement = codec::decode::<OracleAnnouncement>(&bytes)?;
+        anyhow::ensure!(
+            announcement.event.event_id == args.event_id,
+            "got an announcement for event {}",
+            announcement.event.event_id
+        );
+        announcements.push(announcement);
+    }
+    let n_outcomes = announcements
+        .first()
+        .ok_or(Error::NoAnnouncements)?
+        .event
+        .outcome_descriptor
+        .n_outcomes;
+    let contract = payout_contract(n_outcomes, args.n_intervals);
+    let params = Params::from_announcements(
+        &announcements,
+        args.threshold,
+        args.s,
+        OutcomeEncoding::Digits,
+        args.non_interactive,
+        Some(contract.outcome_intervals()),
+    )?;
+    Ok((params, contract))
+}
 
-    println!("got the secret sig {:?}", scalar);
+fn run_alice(args: &PartyArgs) -> anyhow::Result<()> {
+    let (params, contract) = setup_contract(args)?;
+    let (secret_sigs, outcome_images) = contract.gen_secrets(&mut rand::thread_rng());
+    let alice_sk = Scalar::random(&mut rand::thread_rng());
+    let encrypted_signatures =
+        contract.sign_cets(&alice_sk, &outcome_images, &mut rand::thread_rng())?;
 
+    let mut stream = connect(&args.bob)?;
+    send(
+        &mut stream,
+        &CetSignatures {
+            public_key: &alice_sk * &*G,
+            outcome_images,
+            encrypted_signatures,
+        },
+    )?;
+    let (mut session, outgoing) = AliceSession::new(
+        params,
+        secret_sigs,
+        args.non_interactive,
+        &mut rand::thread_rng(),
+    )?;
+    send_all(&mut stream, outgoing)?;
+    while let AliceState::AwaitingMessage2(_) = session.state() {
+        let outgoing = session.handle_message(&receive(&mut stream)?, &mut rand::thread_rng())?;
+        send_all(&mut stream, outgoing)?;
+    }
+    println!("sent Alice's encryptions to Bob");
     Ok(())
 }
 
-fn encode_len(message: &impl Serialize) -> usize {
-    bincode::serde::encode_to_vec(&message, bincode::config::standard())
-        .unwrap()
-        .len()
+fn run_bob(args: &PartyArgs) -> anyhow::Result<()> {
+    let (params, contract) = setup_contract(args)?;
+    let listener = Listener::bind(&args.bob)?;
+    println!("waiting for Alice on {}", args.bob);
+    let mut stream = listener.accept()?;
+
+    let cet_signatures = codec::decode::<CetSignatures>(&receive(&mut stream)?)?;
+    contract.verify_cet_signatures(
+        &cet_signatures.public_key,
+        &cet_signatures.outcome_images,
+        &cet_signatures.encrypted_signatures,
+    )?;
+    let mut session = BobSession::new(
+        params,
+        cet_signatures.outcome_images.clone(),
+        args.non_interactive,
+    );
+    while let BobState::AwaitingMessage1 | BobState::AwaitingMessage3(_) = session.state() {
+        let outgoing = session.handle_message(&receive(&mut stream)?, &mut rand::thread_rng())?;
+        send_all(&mut stream, outgoing)?;
+    }
+    println!("verified Alice's encryptions");
+
+    let mut stream = connect(&args.oracle)?;
+    send(
+        &mut stream,
+        &OracleRequest::Attestations {
+            event_id: args.event_id.clone(),
+        },
+    )?;
+    while let Some(bytes) = read_frame(&mut stream)? {
+        if let Err(e) = session.handle_message(&bytes, &mut rand::thread_rng()) {
+            println!("rejected attestation: {}", e);
+        }
+        if let BobState::Recovered(_) | BobState::Aborted(_) = session.state() {
+            break;
+        }
+    }
+    let recovery = match session.state() {
+        BobState::Recovered(recovery) => recovery,
+        BobState::Aborted(e) => return Err(e.clone().into()),
+        _ => anyhow::bail!("not enough of the oracles attested to the same outcome"),
+    };
+
+    let (payout, signature) =
+        contract.complete_cet(recovery, &cet_signatures.encrypted_signatures)?;
+    anyhow::ensure!(
+        schnorr::verify(
+            &cet_signatures.public_key,
+            &contract.cet_message(recovery.secret_index),
+            &signature
+        ),
+        "the decrypted signature is invalid"
+    );
+    println!(
+        "decrypted Alice's signature on the CET paying Alice {} and Bob {}",
+        payout.alice, payout.bob
+    );
+    Ok(())
 }
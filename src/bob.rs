@@ -1,29 +1,90 @@
-use crate::{common::Params, messages::*, G};
-use anyhow::anyhow;
+use crate::{
+    common::{derive_elgamal_base, OutcomeEncoding, Params, Prefix},
+    error::{Error, Result},
+    messages::*,
+    oracle::OracleAttestation,
+    poly::PointPoly,
+    G,
+};
 use curve25519_dalek::{ristretto::RistrettoPoint as Point, scalar::Scalar};
-use rand::{prelude::SliceRandom, RngCore};
-use zkp::{toolbox::verifier::Verifier, Transcript};
+use rand::{prelude::SliceRandom, CryptoRng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{digest::Digest, Sha256};
+use zkp::toolbox::verifier::Verifier;
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Bob1 {
     commits: Vec<Commit>,
     message2: Message2,
+    params_hash: [u8; 32],
 }
 
 impl Bob1 {
-    pub fn new(message: Message1, params: &Params) -> anyhow::Result<(Bob1, Message2)> {
+    pub fn new(
+        message: Message1,
+        params: &Params,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Result<(Bob1, Message2)> {
+        params.validate()?;
         if message.commits.len() != params.M() {
-            return Err(anyhow!("Alice sent wrong number of commitments"));
+            return Err(Error::WrongNumberOfCommitments {
+                expected: params.M(),
+                got: message.commits.len(),
+            });
         }
-        let message2 = Self::gen_message2(&message.commits, params, &mut rand::thread_rng());
+        let message2 = Self::gen_message2(&message.commits, params, rng);
         Ok((
             Bob1 {
                 commits: message.commits,
                 message2: message2.clone(),
+                params_hash: params.hash(),
             },
             message2,
         ))
     }
 
+    /// Non-interactive version of [`Bob1::new`] where the challenge is derived from `Message1`
+    /// via [`Bob1::gen_message2_non_interactive`] rather than sampled by Bob. The params must be
+    /// built for the non-interactive protocol.
+    pub fn new_non_interactive(message: Message1, params: &Params) -> Result<Bob1> {
+        params.validate()?;
+        if !params.non_interactive {
+            return Err(Error::InteractiveParams);
+        }
+        if message.commits.len() != params.M() {
+            return Err(Error::WrongNumberOfCommitments {
+                expected: params.M(),
+                got: message.commits.len(),
+            });
+        }
+        let message2 = Self::gen_message2_non_interactive(&message.commits, params);
+        Ok(Bob1 {
+            commits: message.commits,
+            message2,
+            params_hash: params.hash(),
+        })
+    }
+
+    /// Derives the cut-and-choose challenge from a hash of the commitments and the params
+    /// (Fiat-Shamir). Alice and Bob both compute this so Alice can send `Message1` and `Message3`
+    /// together without waiting for Bob.
+    ///
+    /// Since Alice can grind on her commitments the params have to account for offline work so
+    /// [`Bob1::new_non_interactive`] only accepts params built with `non_interactive` (see
+    /// [`GRINDING_BITS`](crate::common::GRINDING_BITS)).
+    pub fn gen_message2_non_interactive(commits: &[Commit], params: &Params) -> Message2 {
+        let encoded_commits = bincode::serde::encode_to_vec(commits, bincode::config::standard())
+            .expect("commits can always be encoded");
+        let seed = Sha256::default()
+            .chain(b"dlc-venc-adaptor/fiat-shamir")
+            .chain(params.hash())
+            .chain(encoded_commits)
+            .finalize();
+        let mut rng = ChaCha20Rng::from_seed(seed.into());
+        Self::gen_message2(commits, params, &mut rng)
+    }
+
     pub fn gen_message2(commits: &[Commit], params: &Params, rng: &mut impl RngCore) -> Message2 {
         let indexes = (0..commits.len()).collect::<Vec<_>>();
         let openings = indexes
@@ -45,10 +106,23 @@ impl Bob1 {
         message: Message3,
         outcome_images: Vec<Point>,
         params: &Params,
-    ) -> anyhow::Result<Bob2> {
+    ) -> Result<Bob2> {
+        params.check_hash(&self.params_hash)?;
+        params.validate()?;
+        message.validate(params)?;
+        if outcome_images.len() != params.n_secrets() {
+            return Err(Error::WrongNumberOfOutcomeImages {
+                expected: params.n_secrets(),
+                got: outcome_images.len(),
+            });
+        }
+
+        let elgamal_base = derive_elgamal_base();
+        let mut transcript = crate::dleq::transcript(params, &self.commits, &self.message2);
         let Bob1 {
             mut commits,
             message2,
+            params_hash,
         } = self;
         let mut opened = vec![];
         let mut i = 0;
@@ -56,25 +130,24 @@ impl Bob1 {
         commits.retain(|commit| {
             let open_it = message2.openings.contains(&i);
             if open_it {
-                opened.push(commit.clone());
+                opened.push((i, *commit));
             }
             i += 1;
             !open_it
         });
 
-        for (commit, opening) in opened.iter().zip(message.openings.iter()) {
+        for ((index, commit), opening) in opened.iter().zip(message.openings.iter()) {
             let ri_prime = opening;
             let Ri_prime = ri_prime * &*G;
             if Ri_prime != commit.C.0 {
-                return Err(anyhow!("decommitment was wrong"));
+                return Err(Error::BadCommitmentOpening { index: *index });
             }
-            let ri_mapped = commit.C.1 - ri_prime * params.elgamal_base;
+            let ri_mapped = commit.C.1 - ri_prime * elgamal_base;
             let ri = crate::common::map_G_to_Zq(ri_mapped, commit.pad);
 
+            // the chain scalar must match the chain point
             if &ri * &*G != commit.R {
-                return Err(anyhow!(
-                    "decommitment of chain scalar didn't match chain point"
-                ));
+                return Err(Error::BadCommitmentOpening { index: *index });
             }
         }
 
@@ -90,169 +163,645 @@ impl Bob1 {
             .collect::<Vec<_>>();
         let mut bit_map_encryptions = vec![];
 
-        let mut transcript = Transcript::new(b"dlc-dleqs");
+        let mut polys = message.polys;
+        for (poly, outcome_image) in polys.iter_mut().zip(outcome_images) {
+            poly.push_front(outcome_image);
+        }
+
         let mut verifier = Verifier::new(b"dlc-dleqs", &mut transcript);
 
-        for (oracle_index, bits_window) in buckets
-            .chunks((params.n_outcome_bits() * 2 * params.bucket_size as u32) as usize)
-            .enumerate()
-        {
-            let mut bits: Vec<[_; 2]> = vec![];
-            for (bit_index, bit_window) in bits_window
-                .chunks((2 * params.bucket_size) as usize)
-                .enumerate()
-            {
-                let mut bit_values = vec![];
-                for (bit_value_index, bit_value_window) in
-                    bit_window.chunks((params.bucket_size) as usize).enumerate()
+        let n_digits = params.n_digits();
+        let base = params.digit_base();
+        let mut bucket_windows = buckets.chunks(params.bucket_size as usize);
+        for (oracle_index, oracle_anticipations) in anticipated_attestations.iter().enumerate() {
+            let mut digits = vec![];
+            for (digit_index, digit_anticipations) in oracle_anticipations.iter().enumerate() {
+                let mut digit_values = vec![];
+                for (digit_value_index, anticipated_attestation) in
+                    digit_anticipations.iter().enumerate()
                 {
-                    let T = message.bit_map_images[oracle_index][bit_index][bit_value_index];
-                    let anticipated_attestation =
-                        anticipated_attestations[oracle_index][bit_index][bit_value_index];
+                    let digit_value_window = bucket_windows
+                        .next()
+                        .expect("there is a bucket for every digit value");
+                    let T = match params.outcome_encoding {
+                        OutcomeEncoding::Digits => {
+                            message.bit_map_images[oracle_index][digit_index][digit_value_index]
+                        }
+                        // the digit value is the outcome and its share is encrypted directly
+                        OutcomeEncoding::Direct => {
+                            let secret_index = params
+                                .secret_index(digit_value_index as u32)
+                                .expect("there is a digit value for each outcome");
+                            polys[secret_index].eval(oracle_index as u32 + 1)
+                        }
+                    };
 
-                    let mut bit_value_bucket = vec![];
-                    for (commit, (encryption, padded_T)) in bit_value_window {
+                    let mut digit_value_bucket = vec![];
+                    for (commit, (encryption, padded_T)) in digit_value_window {
                         crate::dleq::verify_eqaulity(
                             &mut verifier,
                             *encryption,
-                            anticipated_attestation,
-                            params.elgamal_base,
+                            *anticipated_attestation,
+                            elgamal_base,
                             commit.C,
                         );
 
                         if T + commit.R != padded_T * &*G {
-                            return Err(anyhow!("padded bit_map wasn't valid"));
+                            return Err(match params.outcome_encoding {
+                                OutcomeEncoding::Digits => Error::BadBitMapPad {
+                                    oracle_index,
+                                    digit_index,
+                                    digit_value: digit_value_index,
+                                },
+                                OutcomeEncoding::Direct => Error::BadSecretShareEncryption {
+                                    oracle_index,
+                                    outcome_index: digit_value_index,
+                                },
+                            });
                         }
 
-                        bit_value_bucket.push(((commit.C.0, *encryption), *padded_T, commit.pad));
+                        digit_value_bucket.push(((commit.C.0, *encryption), *padded_T, commit.pad));
                     }
-                    bit_values.push((bit_value_bucket, T))
+                    digit_values.push((digit_value_bucket, T))
                 }
-                bits.push(bit_values.try_into().unwrap());
+                digits.push(digit_values);
             }
-            bit_map_encryptions.push(bits);
+            bit_map_encryptions.push(digits);
         }
 
         if verifier.verify_batchable(&message.proof).is_err() {
-            return Err(anyhow!(
-                "proof of equality between ciphertext and commitment was invalid"
-            ));
+            return Err(Error::InvalidDleqProof);
+        }
+
+        // Each secret share pad must be the sum of the bit map images for the prefix plus a share
+        // of the polynomial whose constant term is the outcome image (i.e. Feldman VSS) so we find
+        // out about bad shares now rather than after the oracles attest. With the direct encoding
+        // the shares were already checked against the polynomials above and there are no pads.
+        let secret_prefixes = match params.outcome_encoding {
+            OutcomeEncoding::Digits => params.secret_prefixes(),
+            OutcomeEncoding::Direct => vec![],
+        };
+        for (pad_index, (secret_index, prefix)) in secret_prefixes.into_iter().enumerate() {
+            for oracle_index in 0..n_oracles {
+                let bit_map_image = prefix
+                    .digits(base, n_digits)
+                    .map(|(digit_index, digit_value)| {
+                        message.bit_map_images[oracle_index][digit_index][digit_value as usize]
+                    })
+                    .sum::<Point>();
+                let secret_share_image = polys[secret_index].eval(oracle_index as u32 + 1);
+                let secret_share_pad =
+                    &message.secret_share_pads_by_oracle[oracle_index][pad_index];
+                if secret_share_pad * &*G != bit_map_image + secret_share_image {
+                    return Err(Error::BadSecretSharePad {
+                        oracle_index,
+                        pad_index,
+                    });
+                }
+            }
         }
 
         Ok(Bob2 {
             bit_map_encryptions,
             secret_share_pads_by_oracle: message.secret_share_pads_by_oracle,
-            outcome_images,
+            polys,
+            params_hash,
         })
     }
 }
 
+/// A bucket of encryptions of the same bit map scalar (or secret share with the direct encoding)
+/// along with its image.
+type EncryptionBucket = (Vec<((Point, Point), Scalar, [u8; 32])>, Point);
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Bob2 {
     // For every oracle
     bit_map_encryptions: Vec<
-        // For every outcome bit
+        // For every outcome digit
         Vec<
-            // two bit values
-            [(
-                // a bucket of encryptions
-                Vec<((Point, Point), Scalar, [u8; 32])>,
-                // The image of the bit map that is encrypted
-                Point,
-            ); 2],
+            // For every digit value
+            Vec<EncryptionBucket>,
         >,
     >,
-    // The image of the secret that should be revealed for each outcome
+    // The secret share for each prefix padded with the sum of the bit maps for that prefix
     secret_share_pads_by_oracle: Vec<Vec<Scalar>>,
-    outcome_images: Vec<Point>,
+    // The commitment to the secret sharing polynomial for each secret. The constant term is
+    // the image of the secret that should be revealed for its outcomes.
+    polys: Vec<PointPoly>,
+    params_hash: [u8; 32],
+}
+
+/// The result of successfully decrypting an outcome's secret.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recovery {
+    pub secret: Scalar,
+    /// Which of Alice's secrets was recovered
+    pub secret_index: usize,
+    /// The oracles whose attestations couldn't be used to get a valid share and why.
+    pub rejected: Vec<(usize, Error)>,
 }
 
 impl Bob2 {
+    /// Decrypts the secret for the interval (or outcome) containing `outcome_index` from an
+    /// attestation by every oracle in order (see [`Bob2::recover`] to use only some of them).
+    ///
+    /// Every decrypted share is checked against the polynomial commitment so shares from oracles
+    /// that attested to something else (or whose encryptions Alice corrupted) are discarded and
+    /// reported in [`Recovery::rejected`]. Any `threshold` of the remaining shares reconstruct the
+    /// secret.
     pub fn receive_oracle_attestation(
-        self,
+        &self,
         outcome_index: u32,
-        attestations: Vec<Vec<Scalar>>,
+        attestations: &[OracleAttestation],
         params: &Params,
-    ) -> anyhow::Result<Scalar> {
-        let outcome_bits = crate::common::to_bits(outcome_index, params.n_outcome_bits() as usize);
+    ) -> Result<Recovery> {
+        if attestations.len() != params.oracle_keys.len() {
+            return Err(Error::WrongNumberOfAttestations {
+                expected: params.oracle_keys.len(),
+                got: attestations.len(),
+            });
+        }
+        let attestations = attestations.iter().enumerate().collect::<Vec<_>>();
+        self.recover(outcome_index, &attestations, params)
+    }
+
+    /// Like [`Bob2::receive_oracle_attestation`] but with attestations from only some of the
+    /// oracles as `(oracle_index, attestation)` so Bob doesn't have to wait for every oracle.
+    /// There can be at most one attestation from each oracle.
+    pub fn recover(
+        &self,
+        outcome_index: u32,
+        attestations: &[(usize, &OracleAttestation)],
+        params: &Params,
+    ) -> Result<Recovery> {
+        params.check_hash(&self.params_hash)?;
+        params.validate()?;
+        if outcome_index >= params.n_outcomes {
+            return Err(Error::OutcomeOutOfRange {
+                outcome_index,
+                n_outcomes: params.n_outcomes,
+            });
+        }
+        let n_oracles = params.oracle_keys.len();
+        let mut seen = vec![false; n_oracles];
+        for &(oracle_index, _) in attestations {
+            match seen.get_mut(oracle_index) {
+                None => {
+                    return Err(Error::OracleOutOfRange {
+                        oracle_index,
+                        n_oracles,
+                    })
+                }
+                Some(true) => return Err(Error::DuplicateAttestation { oracle_index }),
+                Some(seen) => *seen = true,
+            }
+        }
+
+        let n_digits = params.n_digits();
+        let (pad_index, (secret_index, prefix)) = params
+            .secret_prefixes()
+            .into_iter()
+            .enumerate()
+            .find(|(_, (_, prefix))| prefix.contains(outcome_index, params.digit_base(), n_digits))
+            .expect("params are valid so every outcome is covered by a prefix");
+        let poly = &self.polys[secret_index];
         let mut secret_shares = vec![];
-        for (oracle_index, bit_attestations) in attestations.into_iter().enumerate() {
-            assert_eq!(
-                outcome_bits.len(),
-                bit_attestations.len(),
-                "attestation for oracle didn't have the right number of signatures"
+        let mut rejected = vec![];
+        for &(oracle_index, attestation) in attestations {
+            let x = oracle_index as u32 + 1;
+            let secret_share = self
+                .decrypt_secret_share(
+                    oracle_index,
+                    outcome_index,
+                    attestation,
+                    (pad_index, prefix),
+                    params,
+                )
+                .and_then(|secret_share| {
+                    if &secret_share * &*G == poly.eval(x) {
+                        Ok(secret_share)
+                    } else {
+                        Err(Error::BadSecretShare { oracle_index })
+                    }
+                });
+            match secret_share {
+                Ok(secret_share) => secret_shares.push((Scalar::from(x), secret_share)),
+                Err(e) => rejected.push((oracle_index, e)),
+            }
+        }
+
+        if secret_shares.len() < params.threshold as usize {
+            return Err(Error::InsufficientShares {
+                needed: params.threshold as usize,
+                got: secret_shares.len(),
+                failures: rejected,
+            });
+        }
+
+        let shares = &secret_shares[0..params.threshold as usize];
+        let secret = shares.iter().fold(Scalar::from(0u32), |acc, (x_j, y_j)| {
+            let x_ms = shares
+                .iter()
+                .map(|(x_m, _)| x_m)
+                .filter(|x_m| x_m != &x_j)
+                .collect::<Vec<_>>();
+            let (num, denom) = x_ms.iter().fold(
+                (Scalar::from(1u32), Scalar::from(1u32)),
+                |(acc_n, acc_d), x_m| (acc_n * *x_m, acc_d * (*x_m - x_j)),
             );
+            let lagrange_coeff = num * { denom.invert() };
+            acc + lagrange_coeff * y_j
+        });
+
+        if &secret * &*G != poly.points()[0] {
+            return Err(Error::WrongSecret);
+        }
+
+        Ok(Recovery {
+            secret,
+            secret_index,
+            rejected,
+        })
+    }
 
-            let bit_map_pads = outcome_bits
+    fn decrypt_secret_share(
+        &self,
+        oracle_index: usize,
+        outcome_index: u32,
+        attestation: &OracleAttestation,
+        (pad_index, prefix): (usize, Prefix),
+        params: &Params,
+    ) -> Result<Scalar> {
+        if attestation.outcome_index != outcome_index {
+            return Err(Error::AttestedToDifferentOutcome {
+                oracle_index,
+                outcome_index: attestation.outcome_index,
+            });
+        }
+        attestation.verify(oracle_index, params)?;
+
+        // only the digits fixed by the prefix are needed to unpad the share
+        let mut secret_share_pad = Scalar::zero();
+        for (digit_index, digit_value) in prefix.digits(params.digit_base(), params.n_digits()) {
+            let digit_attestation = attestation.digit_attestations[digit_index];
+            let (outcome_digit_bucket, expected_bit_map_image) =
+                &self.bit_map_encryptions[oracle_index][digit_index][digit_value as usize];
+            // we only need one of the encryptions in the bucket to be what was expected
+            let bit_map_secret = outcome_digit_bucket
                 .iter()
-                .zip(bit_attestations)
-                .enumerate()
-                .map(|(bit_index, (bit_value, bit_attestation))| {
-                    if &bit_attestation * &*G
-                        != params.anticipate_at_index(oracle_index, bit_index as u32, *bit_value)
-                    {
-                        eprintln!("attestation didn't match anticipated attestation");
-                        return None;
+                .find_map(|(encryption, padded_bit_map_secret, pad)| {
+                    let ri_mapped = encryption.1 - digit_attestation * encryption.0;
+                    let ri = crate::common::map_G_to_Zq(ri_mapped, *pad);
+                    let bit_map_secret = padded_bit_map_secret - ri;
+                    let got_bit_map_image = &bit_map_secret * &*G;
+                    if &got_bit_map_image == expected_bit_map_image {
+                        Some(bit_map_secret)
+                    } else {
+                        None
                     }
+                })
+                .ok_or(Error::DecryptionFailed {
+                    oracle_index,
+                    digit_index,
+                })?;
+            secret_share_pad += bit_map_secret;
+        }
+
+        match params.outcome_encoding {
+            OutcomeEncoding::Digits => {
+                Ok(self.secret_share_pads_by_oracle[oracle_index][pad_index] - secret_share_pad)
+            }
+            // what was decrypted was the secret share itself
+            OutcomeEncoding::Direct => Ok(secret_share_pad),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        alice::Alice1,
+        common::AttestationScheme,
+        oracle::OutcomeDescriptor,
+        test_utils::{announce, encrypt_secrets, gen_secrets, rebuild_oracle, setup, setup_with},
+    };
+
+    #[test]
+    fn non_interactive_round_trip() {
+        let (mut oracles, params) = setup(4, 2, 2, 2);
+        let (secret_sigs, outcome_images) = gen_secrets(&params);
+
+        // restored state is rejected with params other than the ones it was created with
+        let (_, other_params) = setup(4, 2, 2, 2);
+        let (alice, m1) = Alice1::new(&params, &mut rand::thread_rng());
+        assert!(matches!(
+            alice.clone().prove_non_interactive(
+                secret_sigs.clone(),
+                &other_params,
+                &mut rand::thread_rng()
+            ),
+            Err(Error::WrongParams)
+        ));
+        let m3 = alice
+            .prove_non_interactive(secret_sigs.clone(), &params, &mut rand::thread_rng())
+            .unwrap();
+        // params chosen for the interactive protocol are too weak
+        let interactive = Params {
+            non_interactive: false,
+            ..params.clone()
+        };
+        assert!(matches!(
+            Bob1::new_non_interactive(m1.clone(), &interactive),
+            Err(Error::InteractiveParams)
+        ));
+        // as are params that only claim to be strong enough
+        let weak = Params {
+            bucket_size: 1,
+            ..params.clone()
+        };
+        assert!(matches!(
+            Bob1::new_non_interactive(m1.clone(), &weak),
+            Err(Error::InsufficientSecurity {
+                security_param: 20,
+                non_interactive: true
+            })
+        ));
+        let m1 = crate::codec::decode(&crate::codec::encode(&m1)).unwrap();
+        let m3 = crate::codec::decode(&crate::codec::encode(&m3)).unwrap();
+        let bob = Bob1::new_non_interactive(m1, &params).unwrap();
+        let bob = bob.receive_message(m3, outcome_images, &params).unwrap();
+        let key = [7u8; 32];
+        let bob: Bob2 = crate::codec::decrypt(
+            &crate::codec::encrypt(&bob, &key, &mut rand::thread_rng()),
+            &key,
+        )
+        .unwrap();
+
+        let attestations = oracles
+            .iter_mut()
+            .map(|oracle| oracle.attest("event", 2).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            bob.receive_oracle_attestation(2, &attestations, &other_params)
+                .unwrap_err(),
+            Error::WrongParams
+        );
+        let recovery = bob
+            .receive_oracle_attestation(2, &attestations, &params)
+            .unwrap();
+        assert_eq!(recovery.secret, secret_sigs[2]);
+        assert_eq!(recovery.rejected, vec![]);
+    }
+
+    #[test]
+    fn schnorr_attestations() {
+        let outcome_descriptor = OutcomeDescriptor {
+            n_outcomes: 8,
+            base: 2,
+            attestation_scheme: AttestationScheme::Schnorr,
+        };
+        let (mut oracles, params) = setup_with(outcome_descriptor, 3, 2);
+        let (secret_sigs, outcome_images) = gen_secrets(&params);
+
+        let (alice, m1) = Alice1::new(&params, &mut rand::thread_rng());
+        let m3 = alice
+            .prove_non_interactive(secret_sigs.clone(), &params, &mut rand::thread_rng())
+            .unwrap();
+        let bob = Bob1::new_non_interactive(m1, &params)
+            .unwrap()
+            .receive_message(m3, outcome_images, &params)
+            .unwrap();
+
+        let outcome_index = 5;
+        let attestations = oracles
+            .iter_mut()
+            .map(|oracle| oracle.attest("event", outcome_index).unwrap())
+            .collect::<Vec<_>>();
+        let recovery = bob
+            .receive_oracle_attestation(outcome_index, &attestations, &params)
+            .unwrap();
+        assert_eq!(recovery.secret, secret_sigs[5]);
+    }
+
+    #[test]
+    fn single_outcome_event() {
+        let (mut oracles, params) = setup(1, 2, 2, 2);
+        assert_eq!(params.n_anticipations_per_oracle(), 1);
+        let (secret_sigs, outcome_images) = gen_secrets(&params);
+
+        let (alice, m1) = Alice1::new(&params, &mut rand::thread_rng());
+        let m3 = alice
+            .prove_non_interactive(secret_sigs.clone(), &params, &mut rand::thread_rng())
+            .unwrap();
+        let bob = Bob1::new_non_interactive(m1, &params)
+            .unwrap()
+            .receive_message(m3, outcome_images, &params)
+            .unwrap();
+
+        let attestations = oracles
+            .iter_mut()
+            .map(|oracle| oracle.attest("event", 0).unwrap())
+            .collect::<Vec<_>>();
+        let recovery = bob
+            .receive_oracle_attestation(0, &attestations, &params)
+            .unwrap();
+        assert_eq!(recovery.secret, secret_sigs[0]);
+    }
 
-                    let (outcome_bit_bucket, expected_bit_map_image) =
-                        &self.bit_map_encryptions[oracle_index][bit_index][*bit_value as usize];
-                    outcome_bit_bucket.iter().find_map(
-                        |(encryption, padded_bit_map_secret, pad)| {
-                            let ri_mapped = encryption.1 - bit_attestation * encryption.0;
-                            let ri = crate::common::map_G_to_Zq(ri_mapped, *pad);
-                            let bit_map_secret = padded_bit_map_secret - ri;
-                            let got_bit_map_image = &bit_map_secret * &*G;
-                            if &got_bit_map_image == expected_bit_map_image {
-                                Some(bit_map_secret)
-                            } else {
-                                eprintln!(
-                                    "we didn't decrypt what was expected -- ignoring that share"
-                                );
-                                None
-                            }
-                        },
-                    )
+    #[test]
+    fn direct_encoding() {
+        let (oracles, announcements) = announce(
+            OutcomeDescriptor {
+                n_outcomes: 3,
+                base: 3,
+                attestation_scheme: AttestationScheme::Linear,
+            },
+            3,
+        );
+        let params = Params::from_announcements(
+            &announcements,
+            2,
+            20,
+            OutcomeEncoding::Direct,
+            false,
+            Some(vec![(0..=0, 0), (1..=2, 1)]),
+        )
+        .unwrap();
+        let (bob, secret_sigs) = encrypt_secrets(&params);
+        assert!(bob.secret_share_pads_by_oracle.is_empty());
+
+        for (outcome_index, secret_index) in [(0, 0), (1, 1), (2, 1)] {
+            let attestations = oracles
+                .iter()
+                .map(|oracle| {
+                    rebuild_oracle(oracle, &params)
+                        .attest("event", outcome_index)
+                        .unwrap()
                 })
-                .collect::<Option<Vec<_>>>();
+                .collect::<Vec<_>>();
+            let recovery = bob
+                .receive_oracle_attestation(outcome_index, &attestations, &params)
+                .unwrap();
+            assert_eq!(recovery.secret, secret_sigs[secret_index]);
+        }
+    }
+
+    #[test]
+    fn base_10_digits() {
+        let (oracles, mut params) = setup(300, 10, 2, 2);
+        params.outcome_intervals = Some(vec![(0..=99, 0), (100..=157, 1), (158..=299, 2)]);
+        let (bob, secret_sigs) = encrypt_secrets(&params);
 
-            let secret_share_pad = match bit_map_pads {
-                Some(bit_map_pads) => bit_map_pads
-                    .into_iter()
-                    .fold(Scalar::zero(), |acc, pad| acc + pad),
-                None => continue,
-            };
+        for (outcome_index, secret_index) in [(42, 0), (157, 1), (158, 2), (299, 2)] {
+            let attestations = oracles
+                .iter()
+                .map(|oracle| {
+                    rebuild_oracle(oracle, &params)
+                        .attest("event", outcome_index)
+                        .unwrap()
+                })
+                .collect::<Vec<_>>();
+            let recovery = bob
+                .receive_oracle_attestation(outcome_index, &attestations, &params)
+                .unwrap();
+            assert_eq!(recovery.secret, secret_sigs[secret_index]);
+        }
+    }
 
-            let secret_share = self.secret_share_pads_by_oracle[oracle_index]
-                [outcome_index as usize]
-                - secret_share_pad;
+    #[test]
+    fn interval_secrets() {
+        let (oracles, mut params) = setup(16, 2, 2, 2);
+        // the outcomes on either side share a secret
+        params.outcome_intervals = Some(vec![(0..=2, 0), (3..=12, 1), (13..=15, 0)]);
+        let mut skipped_secret = params.clone();
+        skipped_secret.outcome_intervals = Some(vec![(0..=2, 0), (3..=12, 2), (13..=15, 0)]);
+        assert_eq!(
+            skipped_secret.validate(),
+            Err(Error::InvalidOutcomeIntervals)
+        );
+        let (bob, secret_sigs) = encrypt_secrets(&params);
+        assert_eq!(bob.secret_share_pads_by_oracle[0].len(), 2 + 4 + 2);
 
-            secret_shares.push((Scalar::from(oracle_index as u32 + 1), secret_share));
+        for (outcome_index, secret_index) in [(0, 0), (2, 0), (3, 1), (9, 1), (12, 1), (15, 0)] {
+            let attestations = oracles
+                .iter()
+                .map(|oracle| {
+                    rebuild_oracle(oracle, &params)
+                        .attest("event", outcome_index)
+                        .unwrap()
+                })
+                .collect::<Vec<_>>();
+            let recovery = bob
+                .receive_oracle_attestation(outcome_index, &attestations, &params)
+                .unwrap();
+            assert_eq!(recovery.secret_index, secret_index);
+            assert_eq!(recovery.secret, secret_sigs[secret_index]);
         }
+    }
 
-        if secret_shares.len() >= params.threshold as usize {
-            let shares = &secret_shares[0..params.threshold as usize];
-
-            let secret = shares.iter().fold(Scalar::from(0u32), |acc, (x_j, y_j)| {
-                let x_ms = shares
-                    .iter()
-                    .map(|(x_m, _)| x_m)
-                    .filter(|x_m| x_m != &x_j)
-                    .collect::<Vec<_>>();
-                let (num, denom) = x_ms.iter().fold(
-                    (Scalar::from(1u32), Scalar::from(1u32)),
-                    |(acc_n, acc_d), x_m| (acc_n * *x_m, acc_d * (*x_m - x_j)),
-                );
-                let lagrange_coeff = num * { denom.invert() };
-                acc + lagrange_coeff * y_j
-            });
+    #[test]
+    fn bad_attestations_are_discarded() {
+        let (mut oracles, params) = setup(4, 2, 4, 2);
+        let (bob, secret_sigs) = encrypt_secrets(&params);
 
-            if &secret * &*G != self.outcome_images[outcome_index as usize] {
-                return Err(anyhow!("the secret we recovered was wrong"));
+        // copies of the oracles that will attest to something else
+        let mut equivocating_oracles = oracles
+            .iter()
+            .map(|oracle| rebuild_oracle(oracle, &params))
+            .collect::<Vec<_>>();
+        let mut attestations = oracles
+            .iter_mut()
+            .map(|oracle| oracle.attest("event", 1).unwrap())
+            .collect::<Vec<_>>();
+        attestations[0] = equivocating_oracles[0].attest("event", 3).unwrap();
+        attestations[2].digit_attestations[0] += Scalar::one();
+
+        let recovery = bob
+            .receive_oracle_attestation(1, &attestations, &params)
+            .unwrap();
+        assert_eq!(recovery.secret, secret_sigs[1]);
+        assert_eq!(
+            recovery.rejected,
+            vec![
+                (
+                    0,
+                    Error::AttestedToDifferentOutcome {
+                        oracle_index: 0,
+                        outcome_index: 3
+                    }
+                ),
+                (
+                    2,
+                    Error::BadAttestation {
+                        oracle_index: 2,
+                        digit_index: 0
+                    }
+                )
+            ]
+        );
+
+        attestations[1] = equivocating_oracles[1].attest("event", 0).unwrap();
+        assert!(matches!(
+            bob.receive_oracle_attestation(1, &attestations, &params),
+            Err(Error::InsufficientShares {
+                needed: 2,
+                got: 1,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn recover_from_some_of_the_oracles() {
+        let (mut oracles, params) = setup(4, 2, 4, 2);
+        let (bob, secret_sigs) = encrypt_secrets(&params);
+
+        let attestation_1 = oracles[1].attest("event", 3).unwrap();
+        let attestation_3 = oracles[3].attest("event", 3).unwrap();
+        let recovery = bob
+            .recover(3, &[(3, &attestation_3), (1, &attestation_1)], &params)
+            .unwrap();
+        assert_eq!(recovery.secret, secret_sigs[3]);
+
+        assert_eq!(
+            bob.recover(3, &[(1, &attestation_1), (1, &attestation_1)], &params)
+                .unwrap_err(),
+            Error::DuplicateAttestation { oracle_index: 1 }
+        );
+        assert_eq!(
+            bob.recover(3, &[(1, &attestation_1), (4, &attestation_3)], &params)
+                .unwrap_err(),
+            Error::OracleOutOfRange {
+                oracle_index: 4,
+                n_oracles: 4
             }
+        );
+        assert!(matches!(
+            bob.recover(3, &[(1, &attestation_1)], &params),
+            Err(Error::InsufficientShares { .. })
+        ));
+    }
 
-            Ok(secret)
-        } else {
-            Err(anyhow!("not enough shares to reconstruct secret!"))
-        }
+    #[test]
+    fn bad_secret_share_pad_is_caught_before_attestation() {
+        let (_, params) = setup(4, 2, 3, 2);
+        let (secret_sigs, outcome_images) = gen_secrets(&params);
+
+        let (alice, m1) = Alice1::new(&params, &mut rand::thread_rng());
+        let (bob, m2) = Bob1::new(m1, &params, &mut rand::thread_rng()).unwrap();
+        let mut m3 = alice
+            .receive_message(m2, secret_sigs, &params, &mut rand::thread_rng())
+            .unwrap();
+        m3.secret_share_pads_by_oracle[1][3] += Scalar::one();
+
+        assert_eq!(
+            bob.receive_message(m3, outcome_images, &params).err(),
+            Some(Error::BadSecretSharePad {
+                oracle_index: 1,
+                pad_index: 3
+            })
+        );
     }
 }
@@ -0,0 +1,331 @@
+//! The canonical wire encoding of protocol messages and party state.
+//!
+//! Every encoded message starts with a version byte and a tag identifying the message type
+//! followed by the bincode encoding of the message. Decoding only accepts the unique canonical
+//! encoding of a message: points and scalars must be canonically encoded, there must be no
+//! trailing bytes and re-encoding the decoded message must give back exactly the same bytes.
+//!
+//! The state of each party between rounds ([`Alice1`], [`Bob1`], [`Bob2`], [`Initiator1`] and
+//! [`Responder1`]) and of an [`Oracle`] is encoded the same way so it can be persisted. Since it
+//! contains secrets it can be encrypted at rest with [`encrypt`] and [`decrypt`]. The state doesn't
+//! include the [`Params`] so they are persisted on their own and validated when decoded. Each
+//! party's state keeps the hash of its params and rejects any others.
+use crate::{
+    alice::Alice1,
+    bob::*,
+    common::Params,
+    contract::CetSignatures,
+    equivocation::EquivocationProof,
+    error::{Error, Result},
+    messages::*,
+    oracle::{Oracle, OracleAnnouncement, OracleAttestation},
+    transport::OracleRequest,
+    two_way::{Initiator1, Responder1},
+};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use rand::{CryptoRng, RngCore};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// The version of the wire format. Bump this when the encoding of any message changes.
+pub const VERSION: u8 = 0;
+
+pub trait WireMessage: Serialize + DeserializeOwned {
+    /// Identifies the message type on the wire so that one message can't be decoded as another.
+    const TAG: u8;
+
+    /// Checks on the decoded message that can't be expressed through its serde implementation.
+    fn check_canonical(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl WireMessage for Message1 {
+    const TAG: u8 = 1;
+}
+
+impl WireMessage for Message2 {
+    const TAG: u8 = 2;
+}
+
+impl WireMessage for Message3 {
+    const TAG: u8 = 3;
+
+    fn check_canonical(&self) -> Result<()> {
+        // The proof keeps its commitments compressed so they aren't checked on deserialization
+        if self
+            .proof
+            .commitments
+            .iter()
+            .any(|commitment| commitment.decompress().is_none())
+        {
+            return Err(Error::NonCanonicalEncoding);
+        }
+        Ok(())
+    }
+}
+
+impl WireMessage for OracleAnnouncement {
+    const TAG: u8 = 4;
+}
+
+impl WireMessage for OracleAttestation {
+    const TAG: u8 = 5;
+}
+
+impl WireMessage for EquivocationProof {
+    const TAG: u8 = 6;
+}
+
+impl WireMessage for TwoWayMessage2 {
+    const TAG: u8 = 7;
+
+    fn check_canonical(&self) -> Result<()> {
+        self.message3.check_canonical()
+    }
+}
+
+impl WireMessage for OracleRequest {
+    const TAG: u8 = 8;
+}
+
+impl WireMessage for CetSignatures {
+    const TAG: u8 = 9;
+}
+
+impl WireMessage for Alice1 {
+    const TAG: u8 = 128;
+}
+
+impl WireMessage for Bob1 {
+    const TAG: u8 = 129;
+}
+
+impl WireMessage for Bob2 {
+    const TAG: u8 = 130;
+}
+
+impl WireMessage for Oracle {
+    const TAG: u8 = 131;
+}
+
+impl WireMessage for Initiator1 {
+    const TAG: u8 = 132;
+}
+
+impl WireMessage for Responder1 {
+    const TAG: u8 = 133;
+}
+
+impl WireMessage for Params {
+    const TAG: u8 = 134;
+
+    fn check_canonical(&self) -> Result<()> {
+        self.validate()
+    }
+}
+
+pub fn encode<M: WireMessage>(message: &M) -> Vec<u8> {
+    let mut bytes = vec![VERSION, M::TAG];
+    bincode::serde::encode_into_std_write(message, &mut bytes, bincode::config::standard())
+        .expect("messages can always be encoded");
+    bytes
+}
+
+pub fn decode<M: WireMessage>(bytes: &[u8]) -> Result<M> {
+    let body = match bytes {
+        [VERSION, tag, body @ ..] if *tag == M::TAG => body,
+        [VERSION, tag, ..] => {
+            return Err(Error::WrongMessageTag {
+                expected: M::TAG,
+                got: *tag,
+            })
+        }
+        [VERSION] => return Err(Error::InvalidEncoding("missing message tag".into())),
+        [version, ..] => return Err(Error::UnsupportedVersion(*version)),
+        [] => return Err(Error::EmptyMessage),
+    };
+
+    let (message, read): (M, usize) =
+        bincode::serde::decode_from_slice(body, bincode::config::standard())
+            .map_err(|e| Error::InvalidEncoding(e.to_string()))?;
+    if read != body.len() {
+        return Err(Error::TrailingBytes(body.len() - read));
+    }
+    message.check_canonical()?;
+    if encode(&message)[2..] != *body {
+        return Err(Error::NonCanonicalEncoding);
+    }
+
+    Ok(message)
+}
+
+const NONCE_LEN: usize = 24;
+
+/// Encodes `message` and encrypts it under `key` with XChaCha20-Poly1305. The version and tag
+/// are authenticated so the ciphertext can't be decrypted as a different type.
+pub fn encrypt<M: WireMessage>(
+    message: &M,
+    key: &[u8; 32],
+    rng: &mut (impl RngCore + CryptoRng),
+) -> Vec<u8> {
+    let mut nonce = [0u8; NONCE_LEN];
+    rng.fill_bytes(&mut nonce);
+    let ciphertext = XChaCha20Poly1305::new(key.into())
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: &encode(message),
+                aad: &[VERSION, M::TAG],
+            },
+        )
+        .expect("encryption cannot fail");
+    [&nonce[..], &ciphertext[..]].concat()
+}
+
+/// Decrypts and decodes something produced by [`encrypt`].
+pub fn decrypt<M: WireMessage>(bytes: &[u8], key: &[u8; 32]) -> Result<M> {
+    if bytes.len() < NONCE_LEN {
+        return Err(Error::StateDecryptionFailed);
+    }
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+    let plaintext = XChaCha20Poly1305::new(key.into())
+        .decrypt(
+            XNonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: &[VERSION, M::TAG],
+            },
+        )
+        .map_err(|_| Error::StateDecryptionFailed)?;
+    decode(&plaintext)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn message2_round_trip() {
+        let message = Message2 {
+            bucket_mapping: vec![2, 0, 1],
+            openings: BTreeSet::from([1, 3]),
+        };
+        let bytes = encode(&message);
+        let decoded = decode::<Message2>(&bytes).unwrap();
+        assert_eq!(decoded.bucket_mapping, message.bucket_mapping);
+        assert_eq!(decoded.openings, message.openings);
+
+        assert!(decode::<Message1>(&bytes).is_err());
+        assert_eq!(decode::<Message2>(&[]).unwrap_err(), Error::EmptyMessage);
+        assert!(matches!(
+            decode::<Message2>(&[VERSION]),
+            Err(Error::InvalidEncoding(_))
+        ));
+        assert_eq!(
+            decode::<Message2>(&[VERSION + 1, Message2::TAG]).unwrap_err(),
+            Error::UnsupportedVersion(VERSION + 1)
+        );
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(decode::<Message2>(&trailing).is_err());
+        // the openings are a set so swapping them still decodes but isn't canonical
+        let mut swapped = bytes;
+        let len = swapped.len();
+        swapped.swap(len - 1, len - 2);
+        assert!(decode::<Message2>(&swapped).is_err());
+    }
+
+    #[test]
+    fn encrypted_round_trip() {
+        let message = Message2 {
+            bucket_mapping: vec![1, 0],
+            openings: BTreeSet::from([4]),
+        };
+        let key = [42u8; 32];
+        let ciphertext = encrypt(&message, &key, &mut rand::thread_rng());
+        let decrypted = decrypt::<Message2>(&ciphertext, &key).unwrap();
+        assert_eq!(decrypted.bucket_mapping, message.bucket_mapping);
+
+        assert!(decrypt::<Message2>(&ciphertext, &[43u8; 32]).is_err());
+        assert!(decrypt::<Message1>(&ciphertext, &key).is_err());
+    }
+
+    #[test]
+    fn non_canonical_points_and_scalars_are_rejected() {
+        let (_, params) = crate::test_utils::setup(8, 2, 3, 2);
+        let (secret_sigs, _) = crate::test_utils::gen_secrets(&params);
+        let (alice, _) = Alice1::new(&params, &mut rand::thread_rng());
+        let message = alice
+            .prove_non_interactive(secret_sigs, &params, &mut rand::thread_rng())
+            .unwrap();
+        let bytes = encode(&message);
+        assert!(decode::<Message3>(&bytes).is_ok());
+
+        // overwrites the first occurrence of `old` in the encoded message
+        let replace = |old: &[u8], new: [u8; 32]| {
+            let at = bytes
+                .windows(old.len())
+                .position(|window| window == old)
+                .unwrap();
+            let mut replaced = bytes.clone();
+            replaced[at..at + old.len()].copy_from_slice(&new);
+            replaced
+        };
+        // 2^256 - 1 is bigger than the group order
+        let unreduced_scalar = replace(message.openings[0].as_bytes(), [0xff; 32]);
+        assert!(matches!(
+            decode::<Message3>(&unreduced_scalar),
+            Err(Error::InvalidEncoding(_))
+        ));
+        // 2^255 - 1 is bigger than the field modulus so it isn't the encoding of any point
+        let mut invalid_point = [0xff; 32];
+        invalid_point[31] = 0x7f;
+        let bad_encryption = replace(
+            message.encryptions[0].0.compress().as_bytes(),
+            invalid_point,
+        );
+        assert!(matches!(
+            decode::<Message3>(&bad_encryption),
+            Err(Error::InvalidEncoding(_))
+        ));
+        let bad_commitment = replace(message.proof.commitments[0].as_bytes(), invalid_point);
+        assert!(matches!(
+            decode::<Message3>(&bad_commitment),
+            Err(Error::NonCanonicalEncoding)
+        ));
+    }
+
+    #[test]
+    fn params_are_validated_when_decoded() {
+        let (_, params) = crate::test_utils::setup(8, 2, 3, 2);
+        let decoded = decode::<Params>(&encode(&params)).unwrap();
+        assert_eq!(decoded.hash(), params.hash());
+
+        let mut missing_nonce = params.clone();
+        missing_nonce.oracle_keys[0].1.pop();
+        assert!(matches!(
+            decode::<Params>(&encode(&missing_nonce)),
+            Err(Error::WrongNumberOfNonces { .. })
+        ));
+        let no_threshold = Params {
+            threshold: 0,
+            ..params.clone()
+        };
+        assert!(matches!(
+            decode::<Params>(&encode(&no_threshold)),
+            Err(Error::InvalidThreshold { .. })
+        ));
+        let nothing_closed = Params {
+            closed_proportion: 0.0,
+            ..params
+        };
+        assert_eq!(
+            decode::<Params>(&encode(&nothing_closed)).unwrap_err(),
+            Error::InvalidCutAndChoose
+        );
+    }
+}
@@ -1,27 +1,257 @@
+use crate::error::{Error, Result};
+use crate::oracle::OracleAnnouncement;
 use curve25519_dalek::{ristretto::RistrettoPoint as Point, scalar::Scalar};
-use sha2::{digest::Digest, Sha256};
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{digest::Digest, Sha256, Sha512};
+use std::{collections::BTreeSet, ops::RangeInclusive};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Params {
+    /// The event the oracles announced. Attestations to any other event are rejected.
+    pub event_id: String,
     pub oracle_keys: Vec<(Point, Vec<Point>)>,
     pub closed_proportion: f64,
     pub bucket_size: u8,
     pub threshold: u16,
     pub n_outcomes: u32,
-    pub elgamal_base: Point,
+    /// The base the oracles attest to the outcome in. Each digit has its own nonce.
+    pub base: u32,
+    pub attestation_scheme: AttestationScheme,
+    pub outcome_encoding: OutcomeEncoding,
+    /// The bits of security `closed_proportion` and `bucket_size` must give (see
+    /// [`cut_and_choose_security`]). [`Params::validate`] rejects params that give less.
+    pub security_param: u8,
+    /// Whether `closed_proportion` and `bucket_size` must be strong enough for the non-interactive
+    /// protocol (see [`GRINDING_BITS`]). Such params can be used interactively too but not the
+    /// other way around.
+    pub non_interactive: bool,
+    /// When set Alice's secrets are for intervals of outcomes rather than for each outcome (e.g.
+    /// for numeric outcomes where the payout only changes a few times). Each interval is given
+    /// with the index of its secret so intervals that aren't next to each other can share one. The
+    /// intervals must be in order and cover `0..n_outcomes` exactly and every secret index up to
+    /// the largest must be used.
+    pub outcome_intervals: Option<Vec<(RangeInclusive<u32>, usize)>>,
+}
+
+/// How an oracle attests to each digit of the outcome with its key `X = x*G` and the digit's nonce
+/// `R = r*G`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AttestationScheme {
+    /// `s = (d+1)*r + x` for digit value `d`.
+    #[default]
+    Linear,
+    /// A Schnorr signature `s = r + H(R, X, m)*x` on the digit message `m` (see
+    /// [`digit_message`]). The challenge is this crate's own [`schnorr_challenge`] over Ristretto
+    /// so oracles must sign with it rather than e.g. BIP-340.
+    Schnorr,
+}
+
+/// How the oracles' attestations are mapped to secret shares.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutcomeEncoding {
+    /// The oracles attest to each digit of the outcome in base [`Params::base`] and Alice encrypts
+    /// a bit map scalar to every digit value. The secret shares are padded with the bit maps.
+    #[default]
+    Digits,
+    /// The oracles attest to the outcome with a single nonce (i.e. a single digit in base
+    /// `n_outcomes`) and Alice encrypts the secret shares directly to each outcome's anticipated
+    /// attestation. This avoids the bit map layer and is cheaper for events with few outcomes.
+    Direct,
+}
+
+/// The outcomes whose `len` most significant digits are `value`.
+///
+/// Alice pads each secret share with the sum of the digit maps of the digits fixed by the prefix
+/// so an interval of outcomes only needs a pad for each prefix in its decomposition.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Prefix {
+    pub value: u32,
+    pub len: u32,
+}
+
+impl Prefix {
+    /// The digits fixed by the prefix as `(digit_index, digit_value)` where the digit index counts
+    /// from the least significant digit like [`to_digits`].
+    pub fn digits(&self, base: u32, n_digits: u32) -> impl Iterator<Item = (usize, u32)> {
+        let free_digits = (n_digits - self.len) as usize;
+        to_digits(self.value, base, self.len as usize)
+            .into_iter()
+            .enumerate()
+            .map(move |(i, digit_value)| (i + free_digits, digit_value))
+    }
+
+    pub fn contains(&self, outcome_index: u32, base: u32, n_digits: u32) -> bool {
+        outcome_index as u64 / (base as u64).pow(n_digits - self.len) == self.value as u64
+    }
+}
+
+/// Decomposes the interval into the fewest prefixes that cover it exactly. Every prefix fixes at
+/// least one digit so that nothing can be decrypted without an attestation.
+pub fn decompose_interval(interval: RangeInclusive<u32>, base: u32, n_digits: u32) -> Vec<Prefix> {
+    let base = base as u64;
+    let mut prefixes = vec![];
+    let mut start = *interval.start() as u64;
+    let end = *interval.end() as u64 + 1;
+    while start < end {
+        // the largest aligned block starting at `start` that doesn't go past the end
+        let mut free_digits = 0;
+        let mut block_size = 1;
+        while free_digits + 1 < n_digits
+            && start % (block_size * base) == 0
+            && start + block_size * base <= end
+        {
+            block_size *= base;
+            free_digits += 1;
+        }
+        prefixes.push(Prefix {
+            value: (start / block_size) as u32,
+            len: n_digits - free_digits,
+        });
+        start += block_size;
+    }
+    prefixes
+}
+
+/// The number of base `base` digits needed to represent every outcome index. There is always at
+/// least one digit so that even a single outcome event needs an attestation.
+pub fn n_digits(n_outcomes: u32, base: u32) -> u32 {
+    let base = base.max(2) as u64;
+    let mut n_digits = 1;
+    let mut n_representable = base;
+    while n_representable < n_outcomes as u64 {
+        n_representable *= base;
+        n_digits += 1;
+    }
+    n_digits
+}
+
+/// The number of values each digit can take in an outcome index below `n_outcomes`. This is `base`
+/// for every digit except possibly the most significant one so that nothing is encrypted to a
+/// digit value that can't be attested to.
+pub fn n_digit_values(n_outcomes: u32, base: u32) -> Vec<u32> {
+    let n_digits = n_digits(n_outcomes, base);
+    let top_digit_weight = (base as u64).pow(n_digits - 1);
+    let top_digit_values = (n_outcomes.max(1) as u64 - 1) / top_digit_weight + 1;
+    (0..n_digits)
+        .map(|digit_index| {
+            if digit_index == n_digits - 1 {
+                top_digit_values.min(base as u64) as u32
+            } else {
+                base
+            }
+        })
+        .collect()
 }
 
 impl Params {
+    /// Builds the params for a contract on an event announced by each oracle after checking the
+    /// announcements are signed by the oracles and are for the same event, maturity and outcomes.
+    /// With [`OutcomeEncoding::Direct`] the oracles must have announced a single nonce.
+    pub fn from_announcements(
+        announcements: &[OracleAnnouncement],
+        threshold: u16,
+        security_param: u8,
+        outcome_encoding: OutcomeEncoding,
+        non_interactive: bool,
+        outcome_intervals: Option<Vec<(RangeInclusive<u32>, usize)>>,
+    ) -> Result<Params> {
+        let first_event = &announcements.first().ok_or(Error::NoAnnouncements)?.event;
+        let outcome_descriptor = first_event.outcome_descriptor;
+        // everything compute_optimal_params relies on has to be checked before calling it
+        if outcome_descriptor.n_outcomes == 0 {
+            return Err(Error::NoOutcomes);
+        }
+        if outcome_descriptor.base < 2 {
+            return Err(Error::InvalidBase(outcome_descriptor.base));
+        }
+        check_threshold(threshold, announcements.len())?;
+        check_distinct_oracles(
+            announcements
+                .iter()
+                .map(|announcement| &announcement.public_key),
+        )?;
+        for (oracle_index, announcement) in announcements.iter().enumerate() {
+            if !announcement.verify() {
+                return Err(Error::BadAnnouncementSignature { oracle_index });
+            }
+            let event = &announcement.event;
+            if event.outcome_descriptor != outcome_descriptor
+                || event.event_id != first_event.event_id
+                || event.maturity != first_event.maturity
+            {
+                return Err(Error::MismatchedAnnouncement { oracle_index });
+            }
+        }
+
+        let mut params = Params {
+            event_id: first_event.event_id.clone(),
+            oracle_keys: announcements
+                .iter()
+                .map(|announcement| (announcement.public_key, announcement.event.nonces.clone()))
+                .collect(),
+            // set below from the digit base
+            closed_proportion: 0.5,
+            bucket_size: 1,
+            threshold,
+            n_outcomes: outcome_descriptor.n_outcomes,
+            base: outcome_descriptor.base,
+            attestation_scheme: outcome_descriptor.attestation_scheme,
+            outcome_encoding,
+            security_param,
+            non_interactive,
+            outcome_intervals,
+        };
+        (params.closed_proportion, params.bucket_size) = params.optimal_cut_and_choose();
+        params.validate()?;
+        Ok(params)
+    }
+
+    /// Checks these are the params that party state was created with (see [`Params::hash`]) since
+    /// the state only makes sense with them.
+    pub(crate) fn check_hash(&self, params_hash: &[u8; 32]) -> Result<()> {
+        if self.hash() != *params_hash {
+            return Err(Error::WrongParams);
+        }
+        Ok(())
+    }
+
+    /// The cheapest `closed_proportion` and `bucket_size` that give `security_param` bits of
+    /// security for these params.
+    fn optimal_cut_and_choose(&self) -> (f64, u8) {
+        compute_optimal_params(
+            self.security_param,
+            self.non_interactive,
+            self.n_outcomes,
+            self.oracle_keys.len() as u32,
+            self.digit_base(),
+        )
+    }
+
     pub fn M(&self) -> usize {
         (self.NB() as f64 / self.closed_proportion).ceil() as usize
     }
 
-    pub fn n_outcome_bits(&self) -> u32 {
-        (self.n_outcomes as f32).log2().ceil() as u32
+    pub fn n_digits(&self) -> u32 {
+        n_digits(self.n_outcomes, self.digit_base())
+    }
+
+    /// The base of the digits the oracles attest to. With [`OutcomeEncoding::Direct`] the whole
+    /// outcome is a single digit.
+    pub fn digit_base(&self) -> u32 {
+        match self.outcome_encoding {
+            OutcomeEncoding::Digits => self.base,
+            OutcomeEncoding::Direct => self.n_outcomes.max(2),
+        }
+    }
+
+    /// The number of values that can occur for each digit (see [`n_digit_values`]).
+    pub fn n_digit_values(&self) -> Vec<u32> {
+        n_digit_values(self.n_outcomes, self.digit_base())
     }
 
     pub fn n_anticipations_per_oracle(&self) -> u32 {
-        self.n_outcome_bits() * 2
+        self.n_digit_values().iter().sum()
     }
 
     pub fn NB(&self) -> usize {
@@ -34,30 +264,243 @@ impl Params {
         self.M() - self.NB()
     }
 
-    pub fn iter_anticipations(&self, oracle_index: usize) -> impl Iterator<Item = [Point; 2]> + '_ {
+    /// Checks there are outcomes, the base and threshold are usable, the cut-and-choose params give
+    /// `security_param` bits of security, each oracle has a nonce for every digit and the outcome
+    /// intervals are in order and cover every outcome exactly once. Params that were deserialized
+    /// must be checked with this before use.
+    pub fn validate(&self) -> Result<()> {
+        if !(self.closed_proportion > 0.0 && self.closed_proportion < 1.0) || self.bucket_size == 0
+        {
+            return Err(Error::InvalidCutAndChoose);
+        }
+        if self.n_outcomes == 0 {
+            return Err(Error::NoOutcomes);
+        }
+        if self.digit_base() < 2 {
+            return Err(Error::InvalidBase(self.digit_base()));
+        }
+        check_threshold(self.threshold, self.oracle_keys.len())?;
+        check_distinct_oracles(self.oracle_keys.iter().map(|(public_key, _)| public_key))?;
+        let security = cut_and_choose_security(
+            self.closed_proportion,
+            self.bucket_size,
+            self.n_outcomes,
+            self.oracle_keys.len() as u32,
+            self.digit_base(),
+        );
+        // allow for rounding since the optimal params meet the bound exactly at best
+        if security + 1e-9 < required_security(self.security_param, self.non_interactive) {
+            return Err(Error::InsufficientSecurity {
+                security_param: self.security_param,
+                non_interactive: self.non_interactive,
+            });
+        }
+        let n_digits = self.n_digits() as usize;
+        for (oracle_index, (_, nonces)) in self.oracle_keys.iter().enumerate() {
+            if nonces.len() != n_digits {
+                return Err(Error::WrongNumberOfNonces {
+                    oracle_index,
+                    expected: n_digits,
+                    got: nonces.len(),
+                });
+            }
+        }
+        if let Some(intervals) = &self.outcome_intervals {
+            let mut next = 0u64;
+            let mut used = vec![false; intervals.len()];
+            for (interval, secret_index) in intervals {
+                if *interval.start() as u64 != next || interval.end() < interval.start() {
+                    return Err(Error::InvalidOutcomeIntervals);
+                }
+                next = *interval.end() as u64 + 1;
+                // there can't be more secrets than intervals
+                match used.get_mut(*secret_index) {
+                    Some(used) => *used = true,
+                    None => return Err(Error::InvalidOutcomeIntervals),
+                }
+            }
+            if next != self.n_outcomes as u64 || used[..self.n_secrets()].contains(&false) {
+                return Err(Error::InvalidOutcomeIntervals);
+            }
+        }
+        Ok(())
+    }
+
+    /// The number of secrets Alice encrypts i.e. one for each outcome or as many as the intervals
+    /// use.
+    pub fn n_secrets(&self) -> usize {
+        match &self.outcome_intervals {
+            Some(intervals) => intervals
+                .iter()
+                .map(|(_, secret_index)| secret_index + 1)
+                .max()
+                .unwrap_or(0),
+            None => self.n_outcomes as usize,
+        }
+    }
+
+    /// The prefix for each secret share pad along with the index of the secret it pads.
+    pub fn secret_prefixes(&self) -> Vec<(usize, Prefix)> {
+        let n_digits = self.n_digits();
+        match &self.outcome_intervals {
+            Some(intervals) => intervals
+                .iter()
+                .flat_map(|(interval, secret_index)| {
+                    decompose_interval(interval.clone(), self.digit_base(), n_digits)
+                        .into_iter()
+                        .map(move |prefix| (*secret_index, prefix))
+                })
+                .collect(),
+            None => (0..self.n_outcomes)
+                .map(|outcome_index| {
+                    let prefix = Prefix {
+                        value: outcome_index,
+                        len: n_digits,
+                    };
+                    (outcome_index as usize, prefix)
+                })
+                .collect(),
+        }
+    }
+
+    /// The index of the secret that is revealed when the oracles attest to `outcome_index`.
+    pub fn secret_index(&self, outcome_index: u32) -> Option<usize> {
+        if outcome_index >= self.n_outcomes {
+            return None;
+        }
+        match &self.outcome_intervals {
+            Some(intervals) => intervals
+                .iter()
+                .find(|(interval, _)| interval.contains(&outcome_index))
+                .map(|(_, secret_index)| *secret_index),
+            None => Some(outcome_index as usize),
+        }
+    }
+
+    /// A digest of every parameter so that challenges can be bound to the exact contract.
+    pub fn hash(&self) -> [u8; 32] {
+        let encoded = bincode::serde::encode_to_vec(self, bincode::config::standard())
+            .expect("params can always be encoded");
+        Sha256::default()
+            .chain(b"dlc-venc-adaptor/params")
+            .chain(encoded)
+            .finalize()
+            .into()
+    }
+
+    /// The anticipated attestation point for every value of each digit.
+    pub fn iter_anticipations(&self, oracle_index: usize) -> impl Iterator<Item = Vec<Point>> + '_ {
         let pk = self.oracle_keys[oracle_index].0;
         let nonces = &self.oracle_keys[oracle_index].1;
-        (0..self.n_outcome_bits()).map(move |bit| {
-            let zero = pk + &nonces[bit as usize];
-            let one = zero + &nonces[bit as usize];
-            [zero, one]
-        })
+        self.n_digit_values()
+            .into_iter()
+            .enumerate()
+            .map(move |(digit, n_values)| {
+                let nonce = nonces[digit];
+                match self.attestation_scheme {
+                    AttestationScheme::Linear => {
+                        std::iter::successors(Some(pk + nonce), |prev| Some(prev + nonce))
+                            .take(n_values as usize)
+                            .collect()
+                    }
+                    AttestationScheme::Schnorr => (0..n_values)
+                        .map(|digit_value| {
+                            anticipate(AttestationScheme::Schnorr, &pk, &nonce, digit_value)
+                        })
+                        .collect(),
+                }
+            })
     }
 
     pub fn anticipate_at_index(
         &self,
         oracle_index: usize,
-        oracle_bit_index: u32,
-        outcome_bit_value: bool,
+        oracle_digit_index: u32,
+        outcome_digit_value: u32,
     ) -> Point {
-        let nonce_coef = Scalar::from(outcome_bit_value as u32 + 1);
         let (oracle_key, oracle_nonces) = &self.oracle_keys[oracle_index];
-        oracle_key + nonce_coef * &oracle_nonces[oracle_bit_index as usize]
+        anticipate(
+            self.attestation_scheme,
+            oracle_key,
+            &oracle_nonces[oracle_digit_index as usize],
+            outcome_digit_value,
+        )
+    }
+}
+
+/// An oracle that appears more than once would get a share for each time it appears.
+fn check_distinct_oracles<'a>(public_keys: impl Iterator<Item = &'a Point>) -> Result<()> {
+    let mut seen = BTreeSet::new();
+    for (oracle_index, public_key) in public_keys.enumerate() {
+        if !seen.insert(public_key.compress().to_bytes()) {
+            return Err(Error::DuplicateOracle { oracle_index });
+        }
+    }
+    Ok(())
+}
+
+fn check_threshold(threshold: u16, n_oracles: usize) -> Result<()> {
+    if threshold == 0 || threshold as usize > n_oracles {
+        return Err(Error::InvalidThreshold {
+            threshold,
+            n_oracles,
+        });
+    }
+    Ok(())
+}
+
+/// The point the oracle's attestation to `digit_value` with `nonce` will be the discrete log of.
+pub fn anticipate(
+    attestation_scheme: AttestationScheme,
+    public_key: &Point,
+    nonce: &Point,
+    digit_value: u32,
+) -> Point {
+    match attestation_scheme {
+        AttestationScheme::Linear => {
+            let nonce_coef = Scalar::from(digit_value + 1);
+            public_key + nonce_coef * nonce
+        }
+        AttestationScheme::Schnorr => {
+            let challenge = schnorr_challenge(nonce, public_key, &digit_message(digit_value));
+            nonce + challenge * public_key
+        }
     }
 }
 
-pub fn map_Zq_to_G(ri: &Scalar) -> (Point, [u8; 32]) {
-    let point = Point::random(&mut rand::thread_rng());
+/// The message a Schnorr oracle signs for a digit i.e. its value in decimal.
+pub fn digit_message(digit_value: u32) -> Vec<u8> {
+    digit_value.to_string().into_bytes()
+}
+
+/// The challenge `H(R, X, m)` of a Schnorr signature on `message` under `public_key`.
+pub fn schnorr_challenge(nonce: &Point, public_key: &Point, message: &[u8]) -> Scalar {
+    Scalar::from_hash(
+        Sha512::default()
+            .chain(b"dlc-venc-adaptor/schnorr-challenge")
+            .chain(nonce.compress().as_bytes())
+            .chain(public_key.compress().as_bytes())
+            .chain(message),
+    )
+}
+
+/// Hashes `tag` to a point with a domain separation prefix unique to this protocol.
+pub fn hash_to_point(tag: &[u8]) -> Point {
+    let input = [b"dlc-venc-adaptor/hash-to-point/".as_ref(), tag].concat();
+    Point::hash_from_bytes::<Sha512>(&input)
+}
+
+/// The second generator `H` of the ElGamal commitments `(r'G, r'H + P)` to each `ri`. The
+/// commitments are binding whatever `H` is but anyone who knows `log_G(H)` can strip `r'H` from
+/// every commitment and learn every `ri` (and so every bit map scalar) without an attestation.
+/// Since the verifier must not know it `H` is always hashed to the group rather than taken from
+/// the params.
+pub fn derive_elgamal_base() -> Point {
+    hash_to_point(b"elgamal-base")
+}
+
+pub fn map_Zq_to_G(ri: &Scalar, rng: &mut (impl RngCore + CryptoRng)) -> (Point, [u8; 32]) {
+    let point = Point::random(rng);
     let mut hashed_xor_ri = Sha256::default()
         .chain(point.compress().to_bytes())
         .finalize();
@@ -77,21 +520,25 @@ pub fn map_G_to_Zq(point: Point, pad: [u8; 32]) -> Scalar {
     Scalar::from_bytes_mod_order(ri_bytes.try_into().unwrap())
 }
 
-pub fn compute_optimal_params(security_param: u8, n_outcomes: u32, n_oracles: u32) -> (f64, u8) {
-    if n_outcomes * n_oracles == 1 {
+/// The bits of work we assume Alice can spend grinding on her commitments in the non-interactive
+/// protocol. Every `Message1` she tries gives her another chance at a challenge that misses her
+/// corrupted encryptions so this is added to the security parameter.
+pub const GRINDING_BITS: u8 = 64;
+
+pub fn compute_optimal_params(
+    security_param: u8,
+    non_interactive: bool,
+    n_outcomes: u32,
+    n_oracles: u32,
+    base: u32,
+) -> (f64, u8) {
+    let security_param = required_security(security_param, non_interactive);
+    if n_outcomes == 1 && n_oracles == 1 {
         // this is cheating and not quite right
-        return (0.5, security_param);
-    }
-    let n_outcomes = n_outcomes as f64;
-    let mut n_encryptions = n_outcomes.log2().ceil() * 2.0;
-    if n_encryptions == 0.0 {
-        n_encryptions = 1.0;
+        return (0.5, security_param as u8);
     }
-    let n_oracles = n_oracles as f64;
-    let N = n_encryptions * n_oracles;
-    // we can afford to remove 1 bit of security since for any corruption the adversary makes there
-    // is a 1/2 chance that that outcome is actually selected.
-    let s = security_param as f64 - 1.0;
+    let (N, digit_value_bits) = cut_and_choose_size(n_outcomes, n_oracles, base);
+    let s = security_param - digit_value_bits;
 
     let (B, p, _) = (500..999)
         .filter_map(|p| {
@@ -112,6 +559,61 @@ pub fn compute_optimal_params(security_param: u8, n_outcomes: u32, n_oracles: u3
     (p, B)
 }
 
+/// The bits of security a `closed_proportion` and `bucket_size` give i.e. the inverse of the
+/// bound [`compute_optimal_params`] picks them with.
+pub fn cut_and_choose_security(
+    closed_proportion: f64,
+    bucket_size: u8,
+    n_outcomes: u32,
+    n_oracles: u32,
+    base: u32,
+) -> f64 {
+    if n_outcomes == 1 && n_oracles == 1 {
+        return bucket_size as f64;
+    }
+    let (N, digit_value_bits) = cut_and_choose_size(n_outcomes, n_oracles, base);
+    let p = closed_proportion;
+    if N < (1.0 / (1.0 - p)) {
+        return 0.0;
+    }
+    bucket_size as f64 * ((N - N * p).log2() - p.log2() / (1.0 - p)) - N.log2()
+        + p.log2()
+        + digit_value_bits
+}
+
+fn required_security(security_param: u8, non_interactive: bool) -> f64 {
+    let grinding_bits = if non_interactive { GRINDING_BITS } else { 0 };
+    security_param as f64 + grinding_bits as f64
+}
+
+/// The number of encryptions that end up in buckets and how many bits of security can be
+/// removed because of the digit values.
+fn cut_and_choose_size(n_outcomes: u32, n_oracles: u32, base: u32) -> (f64, f64) {
+    let n_digit_values = n_digit_values(n_outcomes, base);
+    let n_encryptions = n_digit_values.iter().sum::<u32>() as f64;
+    // we can afford to remove log2(k) bits of security since for any corruption the adversary
+    // makes there is a 1/k chance that that digit value is actually selected where k is the
+    // number of values the digit can take.
+    let min_digit_values = *n_digit_values
+        .iter()
+        .min()
+        .expect("always at least one digit");
+    (
+        n_encryptions * n_oracles as f64,
+        (min_digit_values as f64).log2(),
+    )
+}
+
+pub fn to_digits(mut num: u32, base: u32, n_digits: usize) -> Vec<u32> {
+    (0..n_digits)
+        .map(|_| {
+            let digit = num % base;
+            num /= base;
+            digit
+        })
+        .collect()
+}
+
 pub fn to_bits(mut num: u32, bit_length: usize) -> Vec<bool> {
     (0..bit_length)
         .map(|_| {
@@ -125,6 +627,82 @@ pub fn to_bits(mut num: u32, bit_length: usize) -> Vec<bool> {
 #[cfg(test)]
 mod test {
     use super::*;
+    #[test]
+    fn generators_are_fixed_and_independent() {
+        assert_eq!(hash_to_point(b"G"), crate::G.basepoint());
+        assert_eq!(derive_elgamal_base(), derive_elgamal_base());
+        assert_ne!(derive_elgamal_base(), crate::G.basepoint());
+    }
+
+    #[test]
+    fn interval_decomposition() {
+        assert_eq!(
+            decompose_interval(3..=12, 2, 4),
+            vec![
+                Prefix { value: 3, len: 4 },
+                Prefix { value: 1, len: 2 },
+                Prefix { value: 2, len: 2 },
+                Prefix { value: 12, len: 4 },
+            ]
+        );
+        assert_eq!(
+            decompose_interval(0..=15, 2, 4),
+            vec![Prefix { value: 0, len: 1 }, Prefix { value: 1, len: 1 }]
+        );
+        for outcome_index in 0..16 {
+            let covering = decompose_interval(3..=12, 2, 4)
+                .iter()
+                .filter(|prefix| prefix.contains(outcome_index, 2, 4))
+                .count();
+            assert_eq!(covering, (3..=12).contains(&outcome_index) as usize);
+        }
+        assert_eq!(
+            Prefix { value: 2, len: 2 }.digits(2, 4).collect::<Vec<_>>(),
+            vec![(2, 0), (3, 1)]
+        );
+        assert_eq!(
+            decompose_interval(7..=230, 10, 3),
+            vec![
+                Prefix { value: 7, len: 3 },
+                Prefix { value: 8, len: 3 },
+                Prefix { value: 9, len: 3 },
+                Prefix { value: 1, len: 2 },
+                Prefix { value: 2, len: 2 },
+                Prefix { value: 3, len: 2 },
+                Prefix { value: 4, len: 2 },
+                Prefix { value: 5, len: 2 },
+                Prefix { value: 6, len: 2 },
+                Prefix { value: 7, len: 2 },
+                Prefix { value: 8, len: 2 },
+                Prefix { value: 9, len: 2 },
+                Prefix { value: 1, len: 1 },
+                Prefix { value: 20, len: 2 },
+                Prefix { value: 21, len: 2 },
+                Prefix { value: 22, len: 2 },
+                Prefix { value: 230, len: 3 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_to_digits() {
+        assert_eq!(to_digits(123, 10, 4), vec![3, 2, 1, 0]);
+        assert_eq!(to_digits(0x3, 2, 2), vec![1, 1]);
+        assert_eq!(n_digits(1000, 10), 3);
+        assert_eq!(n_digits(1001, 10), 4);
+        assert_eq!(n_digits(1024, 2), 10);
+        assert_eq!(n_digits(1, 2), 1);
+    }
+
+    #[test]
+    fn digit_values_that_cannot_occur_are_skipped() {
+        assert_eq!(n_digit_values(1, 2), vec![1]);
+        assert_eq!(n_digit_values(5, 2), vec![2, 2, 2]);
+        assert_eq!(n_digit_values(300, 10), vec![10, 10, 3]);
+        assert_eq!(n_digit_values(1000, 10), vec![10, 10, 10]);
+        assert_eq!(n_digit_values(3, 3), vec![3]);
+    }
+
     #[test]
     fn test_to_bits() {
         assert_eq!(to_bits(0x01, 2), vec![true, false]);
@@ -0,0 +1,299 @@
+//! Contracts that pay out according to the outcome of an event.
+//!
+//! Outcomes with the same payout share a transaction (a contract execution transaction or CET)
+//! and so a single secret of Alice's. Alice gives Bob an adaptor signature on each CET encrypted to
+//! the image of its secret and the verifiable encryption makes sure Bob can decrypt the one for the
+//! outcome the oracles attest to.
+use crate::{
+    adaptor::{self, EncryptedSignature},
+    bob::Recovery,
+    error::{Error, Result},
+    schnorr::Signature,
+    G,
+};
+use curve25519_dalek::{ristretto::RistrettoPoint as Point, scalar::Scalar};
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{digest::Digest, Sha256};
+use std::ops::RangeInclusive;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Payout {
+    pub alice: u64,
+    pub bob: u64,
+}
+
+/// Alice's adaptor signatures on the CETs along with what Bob needs to check them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CetSignatures {
+    pub public_key: Point,
+    pub outcome_images: Vec<Point>,
+    pub encrypted_signatures: Vec<EncryptedSignature>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Contract {
+    pub n_outcomes: u32,
+    /// The distinct payouts. Each has a CET and a secret at the same index.
+    pub payouts: Vec<Payout>,
+    /// Each interval of outcomes with the index of its payout. The intervals are in order, cover
+    /// every outcome and neighbouring intervals have different payouts.
+    pub intervals: Vec<(RangeInclusive<u32>, usize)>,
+}
+
+impl Contract {
+    /// Groups neighbouring outcomes with the same payout into a single interval and intervals with
+    /// the same payout under a single CET.
+    pub fn from_payout_function(n_outcomes: u32, payout: impl Fn(u32) -> Payout) -> Contract {
+        let mut payouts: Vec<Payout> = vec![];
+        let mut intervals: Vec<(RangeInclusive<u32>, usize)> = vec![];
+        for outcome_index in 0..n_outcomes {
+            let outcome_payout = payout(outcome_index);
+            match intervals.last_mut() {
+                Some((interval, payout_index)) if payouts[*payout_index] == outcome_payout => {
+                    *interval = *interval.start()..=outcome_index
+                }
+                _ => {
+                    let payout_index = match payouts.iter().position(|p| *p == outcome_payout) {
+                        Some(payout_index) => payout_index,
+                        None => {
+                            payouts.push(outcome_payout);
+                            payouts.len() - 1
+                        }
+                    };
+                    intervals.push((outcome_index..=outcome_index, payout_index))
+                }
+            }
+        }
+        Contract {
+            n_outcomes,
+            payouts,
+            intervals,
+        }
+    }
+
+    /// The intervals to use as [`Params::outcome_intervals`](crate::common::Params) so there is a
+    /// secret for each CET.
+    pub fn outcome_intervals(&self) -> Vec<(RangeInclusive<u32>, usize)> {
+        self.intervals.clone()
+    }
+
+    pub fn payout(&self, outcome_index: u32) -> Option<Payout> {
+        self.intervals
+            .iter()
+            .find(|(interval, _)| interval.contains(&outcome_index))
+            .map(|(_, payout_index)| self.payouts[*payout_index])
+    }
+
+    /// The message signed for the CET of the payout at `secret_index`. It commits to the whole
+    /// contract so signatures can't be used for another contract.
+    pub fn cet_message(&self, secret_index: usize) -> [u8; 32] {
+        let encoded = bincode::serde::encode_to_vec(self, bincode::config::standard())
+            .expect("contracts can always be encoded");
+        Sha256::default()
+            .chain(b"dlc-venc-adaptor/cet")
+            .chain(encoded)
+            .chain((secret_index as u64).to_be_bytes())
+            .finalize()
+            .into()
+    }
+
+    /// Alice's secret for each CET (`secret_sigs`) and their images (`outcome_images`).
+    pub fn gen_secrets(&self, rng: &mut (impl RngCore + CryptoRng)) -> (Vec<Scalar>, Vec<Point>) {
+        let secret_sigs = (0..self.payouts.len())
+            .map(|_| Scalar::random(rng))
+            .collect::<Vec<_>>();
+        let outcome_images = secret_sigs.iter().map(|secret| secret * &*G).collect();
+        (secret_sigs, outcome_images)
+    }
+
+    /// Alice's adaptor signature on each CET encrypted to its outcome image.
+    pub fn sign_cets(
+        &self,
+        sk: &Scalar,
+        outcome_images: &[Point],
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Result<Vec<EncryptedSignature>> {
+        if outcome_images.len() != self.payouts.len() {
+            return Err(Error::WrongNumberOfSecrets {
+                expected: self.payouts.len(),
+                got: outcome_images.len(),
+            });
+        }
+        Ok(outcome_images
+            .iter()
+            .enumerate()
+            .map(|(secret_index, outcome_image)| {
+                adaptor::encrypted_sign(sk, outcome_image, &self.cet_message(secret_index), rng)
+            })
+            .collect())
+    }
+
+    /// Bob checks Alice's adaptor signatures before relying on them.
+    pub fn verify_cet_signatures(
+        &self,
+        pk: &Point,
+        outcome_images: &[Point],
+        encrypted_signatures: &[EncryptedSignature],
+    ) -> Result<()> {
+        for (expected, got) in [
+            (self.payouts.len(), outcome_images.len()),
+            (self.payouts.len(), encrypted_signatures.len()),
+        ] {
+            if expected != got {
+                return Err(Error::WrongNumberOfSecrets { expected, got });
+            }
+        }
+        for (secret_index, (outcome_image, encrypted_signature)) in
+            outcome_images.iter().zip(encrypted_signatures).enumerate()
+        {
+            if !adaptor::verify_encrypted_signature(
+                pk,
+                outcome_image,
+                &self.cet_message(secret_index),
+                encrypted_signature,
+            ) {
+                return Err(Error::BadEncryptedSignature { secret_index });
+            }
+        }
+        Ok(())
+    }
+
+    /// Completes Alice's signature on the CET for the attested outcome with the secret Bob
+    /// recovered from the attestations.
+    pub fn complete_cet(
+        &self,
+        recovery: &Recovery,
+        encrypted_signatures: &[EncryptedSignature],
+    ) -> Result<(Payout, Signature)> {
+        if encrypted_signatures.len() != self.payouts.len() {
+            return Err(Error::WrongNumberOfSecrets {
+                expected: self.payouts.len(),
+                got: encrypted_signatures.len(),
+            });
+        }
+        let payout = self
+            .payouts
+            .get(recovery.secret_index)
+            .ok_or(Error::SecretOutOfRange {
+                secret_index: recovery.secret_index,
+                n_secrets: self.payouts.len(),
+            })?;
+        let signature = adaptor::decrypt_signature(
+            &recovery.secret,
+            &encrypted_signatures[recovery.secret_index],
+        );
+        Ok((*payout, signature))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        alice::Alice1,
+        bob::Bob1,
+        common::{AttestationScheme, OutcomeEncoding, Params},
+        oracle::OutcomeDescriptor,
+        schnorr,
+        test_utils::{announce, EVENT_ID},
+    };
+
+    #[test]
+    fn end_to_end_contract() {
+        // Alice gets everything above 10 up to 20 unless it's above 25
+        let contract = Contract::from_payout_function(32, |outcome_index| {
+            let alice = match outcome_index {
+                26.. => 0,
+                _ => outcome_index.clamp(10, 20) as u64 - 10,
+            };
+            Payout {
+                alice,
+                bob: 10 - alice,
+            }
+        });
+        assert_eq!(contract.payouts.len(), 11);
+        assert_eq!(contract.intervals.len(), 12);
+        assert_eq!(contract.intervals[0], (0..=10, 0));
+        assert_eq!(contract.intervals[10], (20..=25, 10));
+        assert_eq!(contract.intervals[11], (26..=31, 0));
+
+        let outcome_descriptor = OutcomeDescriptor {
+            n_outcomes: 32,
+            base: 2,
+            attestation_scheme: AttestationScheme::Linear,
+        };
+        let (mut oracles, announcements) = announce(outcome_descriptor, 3);
+        let params = Params::from_announcements(
+            &announcements,
+            2,
+            20,
+            OutcomeEncoding::Digits,
+            true,
+            Some(contract.outcome_intervals()),
+        )
+        .unwrap();
+
+        let alice_sk = Scalar::random(&mut rand::thread_rng());
+        let (secret_sigs, outcome_images) = contract.gen_secrets(&mut rand::thread_rng());
+        let encrypted_signatures = contract
+            .sign_cets(&alice_sk, &outcome_images, &mut rand::thread_rng())
+            .unwrap();
+        contract
+            .verify_cet_signatures(&(&alice_sk * &*G), &outcome_images, &encrypted_signatures)
+            .unwrap();
+        assert_eq!(
+            contract
+                .sign_cets(&alice_sk, &outcome_images[1..], &mut rand::thread_rng())
+                .unwrap_err(),
+            Error::WrongNumberOfSecrets {
+                expected: 11,
+                got: 10
+            }
+        );
+
+        let (alice, m1) = Alice1::new(&params, &mut rand::thread_rng());
+        let m3 = alice
+            .prove_non_interactive(secret_sigs, &params, &mut rand::thread_rng())
+            .unwrap();
+        let bob = Bob1::new_non_interactive(m1, &params)
+            .unwrap()
+            .receive_message(m3, outcome_images, &params)
+            .unwrap();
+
+        let attestations = oracles
+            .iter_mut()
+            .map(|oracle| oracle.attest(EVENT_ID, 28).unwrap())
+            .collect::<Vec<_>>();
+        let recovery = bob
+            .receive_oracle_attestation(28, &attestations, &params)
+            .unwrap();
+        // the same CET as the outcomes up to 10
+        assert_eq!(recovery.secret_index, 0);
+        let (payout, signature) = contract
+            .complete_cet(&recovery, &encrypted_signatures)
+            .unwrap();
+        assert_eq!(payout, Payout { alice: 0, bob: 10 });
+        let out_of_range = Recovery {
+            secret_index: 11,
+            ..recovery.clone()
+        };
+        assert_eq!(
+            contract
+                .complete_cet(&out_of_range, &encrypted_signatures)
+                .unwrap_err(),
+            Error::SecretOutOfRange {
+                secret_index: 11,
+                n_secrets: 11
+            }
+        );
+        assert!(contract
+            .complete_cet(&recovery, &encrypted_signatures[1..])
+            .is_err());
+        assert!(schnorr::verify(
+            &(&alice_sk * &*G),
+            &contract.cet_message(recovery.secret_index),
+            &signature
+        ));
+    }
+}
@@ -1,12 +1,37 @@
-use crate::G;
+use crate::{
+    common::Params,
+    messages::{Commit, Message2},
+    G,
+};
 use curve25519_dalek::{ristretto::RistrettoPoint as Point, scalar::Scalar};
+use sha2::{digest::Digest, Sha256};
 use zkp::{
     toolbox::{prover::Prover, verifier::Verifier, SchnorrCS},
-    BatchableProof,
+    BatchableProof, Transcript,
 };
 
 pub type Proof = BatchableProof;
 
+/// The transcript for the batched proof. It is bound to the params (and so the oracle keys), the
+/// commitments and the cut-and-choose challenge so a proof from one session can't be replayed or
+/// mixed into another.
+pub fn transcript(params: &Params, commits: &[Commit], message2: &Message2) -> Transcript {
+    let config = bincode::config::standard();
+    let contract_hash = Sha256::default()
+        .chain(params.hash())
+        .chain(
+            bincode::serde::encode_to_vec(commits, config).expect("commits can always be encoded"),
+        )
+        .chain(
+            bincode::serde::encode_to_vec(message2, config)
+                .expect("message2 can always be encoded"),
+        )
+        .finalize();
+    let mut transcript = Transcript::new(b"dlc-dleqs");
+    transcript.append_message(b"contract", &contract_hash);
+    transcript
+}
+
 pub fn prove_eqaulity(
     prover: &mut Prover<'_>,
     ri_prime: Scalar,
@@ -0,0 +1,169 @@
+//! Proofs that an oracle attested to two different values of the same digit of an event.
+//!
+//! Each digit has its own nonce so attesting to two values of it reveals the oracle's secret key.
+//! The proof consists of the signed announcement and the two attestations to the digit so anyone
+//! can check it (and extract the key) without trusting whoever published it.
+use crate::{
+    common::{anticipate, digit_message, schnorr_challenge, to_digits, AttestationScheme},
+    error::{Error, Result},
+    oracle::{OracleAnnouncement, OracleAttestation},
+    G,
+};
+use curve25519_dalek::scalar::Scalar;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EquivocationProof {
+    pub announcement: OracleAnnouncement,
+    pub digit_index: u32,
+    /// The two conflicting attestations to the digit as `(digit_value, attestation)`.
+    pub attestations: [(u32, Scalar); 2],
+}
+
+impl EquivocationProof {
+    /// Finds a digit the two attestations disagree on and returns a proof of the equivocation
+    /// along with the oracle's secret key extracted from it.
+    pub fn new(
+        announcement: OracleAnnouncement,
+        attestation_1: &OracleAttestation,
+        attestation_2: &OracleAttestation,
+    ) -> Result<(EquivocationProof, Scalar)> {
+        let event = &announcement.event;
+        if attestation_1.event_id != event.event_id || attestation_2.event_id != event.event_id {
+            return Err(Error::NoEquivocation);
+        }
+        let n_digits = event.nonces.len();
+        let base = event.outcome_descriptor.base;
+        let digits_1 = to_digits(attestation_1.outcome_index, base, n_digits);
+        let digits_2 = to_digits(attestation_2.outcome_index, base, n_digits);
+        let digit_index = digits_1
+            .iter()
+            .zip(&digits_2)
+            .position(|(digit_1, digit_2)| digit_1 != digit_2)
+            .ok_or(Error::NoEquivocation)?;
+        let (digit_attestation_1, digit_attestation_2) = match (
+            attestation_1.digit_attestations.get(digit_index),
+            attestation_2.digit_attestations.get(digit_index),
+        ) {
+            (Some(digit_attestation_1), Some(digit_attestation_2)) => {
+                (*digit_attestation_1, *digit_attestation_2)
+            }
+            _ => return Err(Error::InvalidEquivocationProof),
+        };
+
+        let proof = EquivocationProof {
+            announcement,
+            digit_index: digit_index as u32,
+            attestations: [
+                (digits_1[digit_index], digit_attestation_1),
+                (digits_2[digit_index], digit_attestation_2),
+            ],
+        };
+        let secret_key = proof.verify()?;
+        Ok((proof, secret_key))
+    }
+
+    /// Checks the announcement is signed by the oracle and that both attestations are valid for
+    /// different values of the same digit. Returns the oracle's secret key.
+    pub fn verify(&self) -> Result<Scalar> {
+        if !self.announcement.verify() {
+            return Err(Error::InvalidEquivocationProof);
+        }
+        let public_key = self.announcement.public_key;
+        let event = &self.announcement.event;
+        let nonce = event
+            .nonces
+            .get(self.digit_index as usize)
+            .ok_or(Error::InvalidEquivocationProof)?;
+        let attestation_scheme = event.outcome_descriptor.attestation_scheme;
+        let [(digit_value_1, s_1), (digit_value_2, s_2)] = self.attestations;
+        if digit_value_1 == digit_value_2 {
+            return Err(Error::InvalidEquivocationProof);
+        }
+        for (digit_value, s) in self.attestations {
+            if digit_value >= event.outcome_descriptor.base
+                || &s * &*G != anticipate(attestation_scheme, &public_key, nonce, digit_value)
+            {
+                return Err(Error::InvalidEquivocationProof);
+            }
+        }
+
+        let secret_key = match attestation_scheme {
+            // s_i = (d_i + 1)*r + x
+            AttestationScheme::Linear => {
+                let d_1 = Scalar::from(digit_value_1);
+                let d_2 = Scalar::from(digit_value_2);
+                let r = (s_1 - s_2) * (d_1 - d_2).invert();
+                s_1 - (d_1 + Scalar::one()) * r
+            }
+            // s_i = r + c_i*x
+            AttestationScheme::Schnorr => {
+                let c_1 = schnorr_challenge(nonce, &public_key, &digit_message(digit_value_1));
+                let c_2 = schnorr_challenge(nonce, &public_key, &digit_message(digit_value_2));
+                (s_1 - s_2) * (c_1 - c_2).invert()
+            }
+        };
+
+        if &secret_key * &*G != public_key {
+            return Err(Error::InvalidEquivocationProof);
+        }
+        Ok(secret_key)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::oracle::{Oracle, OutcomeDescriptor};
+
+    #[test]
+    fn equivocation_reveals_secret_key() {
+        for attestation_scheme in [AttestationScheme::Linear, AttestationScheme::Schnorr] {
+            let outcome_descriptor = OutcomeDescriptor {
+                n_outcomes: 100,
+                base: 10,
+                attestation_scheme,
+            };
+            let seed = rand::random();
+            let mut oracle = Oracle::from_seed(seed);
+            let announcement = oracle
+                .announce(
+                    "btc-usd".into(),
+                    outcome_descriptor,
+                    0,
+                    &mut rand::thread_rng(),
+                )
+                .unwrap();
+            // the oracle rebuilt from its seed doesn't know what the original attested to
+            let mut equivocating_oracle = Oracle::from_seed(seed);
+            equivocating_oracle
+                .announce(
+                    "btc-usd".into(),
+                    outcome_descriptor,
+                    0,
+                    &mut rand::thread_rng(),
+                )
+                .unwrap();
+            let attestation = oracle.attest("btc-usd", 42).unwrap();
+
+            let (proof, secret_key) = EquivocationProof::new(
+                announcement.clone(),
+                &attestation,
+                &equivocating_oracle.attest("btc-usd", 47).unwrap(),
+            )
+            .unwrap();
+            assert_eq!(proof.digit_index, 0);
+            assert_eq!(&secret_key * &*G, oracle.public_key());
+            assert_eq!(proof.verify(), Ok(secret_key));
+
+            let mut forged = proof.clone();
+            forged.attestations[1].0 = 8;
+            assert_eq!(forged.verify(), Err(Error::InvalidEquivocationProof));
+
+            assert_eq!(
+                EquivocationProof::new(announcement, &attestation, &attestation),
+                Err(Error::NoEquivocation)
+            );
+        }
+    }
+}
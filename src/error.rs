@@ -0,0 +1,177 @@
+use thiserror::Error;
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Everything that can go wrong when running the protocol against an adversarial counterparty or
+/// oracle.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum Error {
+    #[error("there must be an announcement from at least one oracle")]
+    NoAnnouncements,
+    #[error("the announcement from oracle {oracle_index} has an invalid signature")]
+    BadAnnouncementSignature { oracle_index: usize },
+    #[error("the announcement from oracle {oracle_index} describes a different event")]
+    MismatchedAnnouncement { oracle_index: usize },
+    #[error(
+        "the announcement from oracle {oracle_index} should have {expected} nonces but has {got}"
+    )]
+    WrongNumberOfNonces {
+        oracle_index: usize,
+        expected: usize,
+        got: usize,
+    },
+    #[error("event {event_id} was already announced differently")]
+    EventAlreadyAnnounced { event_id: String },
+    #[error("event {event_id} hasn't been announced")]
+    UnknownEvent { event_id: String },
+    #[error("event {event_id} was already attested to outcome {outcome_index}")]
+    AlreadyAttested {
+        event_id: String,
+        outcome_index: u32,
+    },
+    #[error("the attestations don't attest to different values of a digit of the same event")]
+    NoEquivocation,
+    #[error("the equivocation proof is invalid")]
+    InvalidEquivocationProof,
+    #[error("the encrypted signature on the transaction for secret {secret_index} is invalid")]
+    BadEncryptedSignature { secret_index: usize },
+    #[error("the attestation isn't from any of the oracles")]
+    UnknownOracle,
+    #[error("the session has finished or been aborted")]
+    SessionClosed,
+    #[error("the event must have at least one outcome")]
+    NoOutcomes,
+    #[error("base must be at least 2 but was {0}")]
+    InvalidBase(u32),
+    #[error("oracle {oracle_index} has the same key as an earlier oracle")]
+    DuplicateOracle { oracle_index: usize },
+    #[error("the threshold must be between 1 and the number of oracles ({n_oracles}) but was {threshold}")]
+    InvalidThreshold { threshold: u16, n_oracles: usize },
+    #[error(
+        "outcome intervals must be in order, cover every outcome exactly once and use every secret"
+    )]
+    InvalidOutcomeIntervals,
+    #[error("secret {secret_index} is outside of range 0..{n_secrets}")]
+    SecretOutOfRange {
+        secret_index: usize,
+        n_secrets: usize,
+    },
+    #[error("the closed proportion must be strictly between 0 and 1 and the bucket size non-zero")]
+    InvalidCutAndChoose,
+    #[error("the cut-and-choose params give less than {security_param} bits of security (non-interactive: {non_interactive})")]
+    InsufficientSecurity {
+        security_param: u8,
+        non_interactive: bool,
+    },
+    #[error("the state was created with different params")]
+    WrongParams,
+    #[error("the params weren't built for the non-interactive protocol")]
+    InteractiveParams,
+    #[error("expected {expected} commitments but got {got}")]
+    WrongNumberOfCommitments { expected: usize, got: usize },
+    #[error("bucket mapping had length {got} but expected {expected}")]
+    WrongBucketMappingLength { expected: usize, got: usize },
+    #[error("bucket was mapped to {index} which is outside of range 0..{NB}")]
+    #[allow(non_snake_case)]
+    BucketMappingOutOfRange { index: usize, NB: usize },
+    #[error("more than one bucket was mapped to {index}")]
+    DuplicateBucketMapping { index: usize },
+    #[error("wrong number of openings requested. Expected {expected} got {got}")]
+    WrongNumberOfOpenings { expected: usize, got: usize },
+    #[error("opening of commitment {index} requested which is outside of range 0..{M}")]
+    #[allow(non_snake_case)]
+    OpeningOutOfRange { index: usize, M: usize },
+    #[error("expected {expected} secrets but got {got}")]
+    WrongNumberOfSecrets { expected: usize, got: usize },
+    #[error("{field} had length {got} but expected {expected}")]
+    MalformedMessage {
+        field: &'static str,
+        expected: usize,
+        got: usize,
+    },
+    #[error("expected {expected} outcome images but got {got}")]
+    WrongNumberOfOutcomeImages { expected: usize, got: usize },
+    #[error("opening of commitment {index} was wrong")]
+    BadCommitmentOpening { index: usize },
+    #[error("proof of equality between ciphertext and commitment was invalid")]
+    InvalidDleqProof,
+    #[error(
+        "padded bit map for oracle {oracle_index} digit {digit_index} value {digit_value} wasn't valid"
+    )]
+    BadBitMapPad {
+        oracle_index: usize,
+        digit_index: usize,
+        digit_value: usize,
+    },
+    #[error("the encryption of the secret share for outcome {outcome_index} to oracle {oracle_index} is inconsistent with the polynomial commitment")]
+    BadSecretShareEncryption {
+        oracle_index: usize,
+        outcome_index: usize,
+    },
+    #[error("secret share pad {pad_index} for oracle {oracle_index} was inconsistent with the polynomial commitment")]
+    BadSecretSharePad {
+        oracle_index: usize,
+        pad_index: usize,
+    },
+    #[error("the attestation is for event {got} rather than {expected}")]
+    WrongEvent { expected: String, got: String },
+    #[error("oracle {oracle_index} is outside of range 0..{n_oracles}")]
+    OracleOutOfRange {
+        oracle_index: usize,
+        n_oracles: usize,
+    },
+    #[error("more than one attestation from oracle {oracle_index}")]
+    DuplicateAttestation { oracle_index: usize },
+    #[error("outcome {outcome_index} is outside of range 0..{n_outcomes}")]
+    OutcomeOutOfRange { outcome_index: u32, n_outcomes: u32 },
+    #[error("expected attestations from {expected} oracles but got {got}")]
+    WrongNumberOfAttestations { expected: usize, got: usize },
+    #[error("attestation from oracle {oracle_index} had {got} signatures but expected {expected}")]
+    WrongAttestationLength {
+        oracle_index: usize,
+        expected: usize,
+        got: usize,
+    },
+    #[error("oracle {oracle_index} attested to outcome {outcome_index} instead")]
+    AttestedToDifferentOutcome {
+        oracle_index: usize,
+        outcome_index: u32,
+    },
+    #[error("attestation from oracle {oracle_index} for digit {digit_index} didn't match the anticipated attestation")]
+    BadAttestation {
+        oracle_index: usize,
+        digit_index: usize,
+    },
+    #[error(
+        "no encryption for oracle {oracle_index} digit {digit_index} decrypted to the bit map"
+    )]
+    DecryptionFailed {
+        oracle_index: usize,
+        digit_index: usize,
+    },
+    #[error("decrypted share from oracle {oracle_index} was inconsistent with the polynomial commitment")]
+    BadSecretShare { oracle_index: usize },
+    #[error("only got {got} of the {needed} shares needed to reconstruct the secret")]
+    InsufficientShares {
+        needed: usize,
+        got: usize,
+        /// The oracles whose shares couldn't be used and why
+        failures: Vec<(usize, Error)>,
+    },
+    #[error("the secret we recovered was wrong")]
+    WrongSecret,
+    #[error("empty message")]
+    EmptyMessage,
+    #[error("unsupported wire version {0}")]
+    UnsupportedVersion(u8),
+    #[error("expected message with tag {expected} but got {got}")]
+    WrongMessageTag { expected: u8, got: u8 },
+    #[error("invalid message encoding: {0}")]
+    InvalidEncoding(String),
+    #[error("{0} trailing bytes after message")]
+    TrailingBytes(usize),
+    #[error("message was not canonically encoded")]
+    NonCanonicalEncoding,
+    #[error("failed to decrypt state")]
+    StateDecryptionFailed,
+}
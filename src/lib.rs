@@ -1,14 +1,28 @@
 #![allow(non_snake_case)]
+pub mod adaptor;
 pub mod alice;
 pub mod bob;
+pub mod codec;
 pub mod common;
+pub mod contract;
 pub mod dleq;
+pub mod equivocation;
+pub mod error;
 pub mod messages;
 pub mod oracle;
 pub mod poly;
+pub mod schnorr;
+pub mod session;
+#[cfg(test)]
+mod test_utils;
+pub mod transport;
+pub mod two_way;
 
 lazy_static::lazy_static! {
+    /// The generator for every key, nonce and commitment in the protocol. It is derived by
+    /// hashing to the group so that independent processes agree on it and nobody knows its
+    /// discrete log with respect to any other generator.
     pub static ref G: curve25519_dalek::ristretto::RistrettoBasepointTable = {
-        curve25519_dalek::ristretto::RistrettoBasepointTable::create(&curve25519_dalek::ristretto::RistrettoPoint::random(&mut rand::thread_rng()))
+        curve25519_dalek::ristretto::RistrettoBasepointTable::create(&common::hash_to_point(b"G"))
     };
 }
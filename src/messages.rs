@@ -1,33 +1,215 @@
+use crate::common::{OutcomeEncoding, Params};
+use crate::error::{Error, Result};
 use crate::poly::PointPoly;
 use curve25519_dalek::{ristretto::RistrettoPoint as Point, scalar::Scalar};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::collections::BTreeSet;
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message1 {
     pub commits: Vec<Commit>,
 }
 
-#[derive(Debug, Clone, Default, Copy, Serialize)]
+#[derive(Debug, Clone, Default, Copy, Serialize, Deserialize)]
 pub struct Commit {
     pub C: (Point, Point),
     pub R: Point,
     pub pad: [u8; 32],
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message2 {
     pub bucket_mapping: Vec<usize>,
     pub openings: BTreeSet<usize>,
 }
 
-#[derive(Clone, Serialize)]
+impl Message2 {
+    /// Checks that the bucket mapping is a permutation of the unopened commitments and that the
+    /// openings are for distinct commitments that exist. Alice must do this before answering
+    /// since anything else could make her reveal more about a commitment than intended.
+    pub fn validate(&self, params: &Params) -> Result<()> {
+        let NB = params.NB();
+        if self.bucket_mapping.len() != NB {
+            return Err(Error::WrongBucketMappingLength {
+                expected: NB,
+                got: self.bucket_mapping.len(),
+            });
+        }
+        let mut seen = vec![false; NB];
+        for &index in &self.bucket_mapping {
+            match seen.get_mut(index) {
+                None => return Err(Error::BucketMappingOutOfRange { index, NB }),
+                Some(true) => return Err(Error::DuplicateBucketMapping { index }),
+                Some(seen) => *seen = true,
+            }
+        }
+
+        if self.openings.len() != params.num_openings() {
+            return Err(Error::WrongNumberOfOpenings {
+                expected: params.num_openings(),
+                got: self.openings.len(),
+            });
+        }
+        let M = params.M();
+        if let Some(&index) = self.openings.iter().find(|index| **index >= M) {
+            return Err(Error::OpeningOutOfRange { index, M });
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Message3 {
     pub proof: crate::dleq::Proof,
     pub encryptions: Vec<(Point, Scalar)>,
     pub polys: Vec<PointPoly>,
     pub openings: Vec<Scalar>,
-    pub bit_map_images: Vec<Vec<[Point; 2]>>,
-    // there is one of these per outcome ( per oracle )
+    // the image of the bit map scalar for every value of every digit ( per oracle ). Empty with
+    // `OutcomeEncoding::Direct`.
+    pub bit_map_images: Vec<Vec<Vec<Point>>>,
+    // there is one of these per secret prefix ( per oracle ). Empty with `OutcomeEncoding::Direct`.
     pub secret_share_pads_by_oracle: Vec<Vec<Scalar>>,
 }
+
+/// The responder's message in a [two-way session](crate::two_way). It has the challenge for the
+/// initiator's `Message1` along with the responder's own `Message1` and `Message3`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TwoWayMessage2 {
+    pub message2: Message2,
+    pub message1: Message1,
+    pub message3: Message3,
+}
+
+impl Message3 {
+    /// Checks that every field has the shape implied by `params` so that verifying the message
+    /// covers everything in it.
+    pub fn validate(&self, params: &Params) -> Result<()> {
+        let n_oracles = params.oracle_keys.len();
+        let n_secrets = params.n_secrets();
+        let n_pads = params.secret_prefixes().len();
+        let n_digit_values = params.n_digit_values();
+        // the encryptions are of the secret shares themselves when the encoding is direct
+        let n_bit_map_oracles = match params.outcome_encoding {
+            OutcomeEncoding::Digits => n_oracles,
+            OutcomeEncoding::Direct => 0,
+        };
+        let n_poly_coefficients = (params.threshold as usize).saturating_sub(1);
+
+        check_len("openings", params.num_openings(), self.openings.len())?;
+        check_len("encryptions", params.NB(), self.encryptions.len())?;
+        check_len("polys", n_secrets, self.polys.len())?;
+        for poly in &self.polys {
+            check_len("poly", n_poly_coefficients, poly.poly_len())?;
+        }
+        check_len(
+            "bit_map_images",
+            n_bit_map_oracles,
+            self.bit_map_images.len(),
+        )?;
+        for oracle_bit_map_images in &self.bit_map_images {
+            check_len(
+                "bit_map_images",
+                n_digit_values.len(),
+                oracle_bit_map_images.len(),
+            )?;
+            for (digit_bit_map_images, n_values) in
+                oracle_bit_map_images.iter().zip(&n_digit_values)
+            {
+                check_len(
+                    "bit_map_images",
+                    *n_values as usize,
+                    digit_bit_map_images.len(),
+                )?;
+            }
+        }
+        check_len(
+            "secret_share_pads_by_oracle",
+            n_bit_map_oracles,
+            self.secret_share_pads_by_oracle.len(),
+        )?;
+        for secret_share_pads in &self.secret_share_pads_by_oracle {
+            check_len(
+                "secret_share_pads_by_oracle",
+                n_pads,
+                secret_share_pads.len(),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+fn check_len(field: &'static str, expected: usize, got: usize) -> Result<()> {
+    if expected != got {
+        return Err(Error::MalformedMessage {
+            field,
+            expected,
+            got,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        common::AttestationScheme,
+        oracle::{Oracle, OutcomeDescriptor},
+    };
+
+    #[test]
+    fn message2_validation() {
+        let outcome_descriptor = OutcomeDescriptor {
+            n_outcomes: 2,
+            base: 2,
+            attestation_scheme: AttestationScheme::Linear,
+        };
+        let announcement = Oracle::random(&mut rand::thread_rng())
+            .announce(
+                "event".into(),
+                outcome_descriptor,
+                0,
+                &mut rand::thread_rng(),
+            )
+            .unwrap();
+        let params = Params {
+            event_id: "event".into(),
+            oracle_keys: vec![(announcement.public_key, announcement.event.nonces)],
+            closed_proportion: 0.5,
+            bucket_size: 2,
+            threshold: 1,
+            n_outcomes: 2,
+            base: 2,
+            attestation_scheme: AttestationScheme::Linear,
+            outcome_encoding: OutcomeEncoding::Digits,
+            security_param: 2,
+            non_interactive: false,
+            outcome_intervals: None,
+        };
+        let valid = Message2 {
+            bucket_mapping: vec![3, 1, 0, 2],
+            openings: BTreeSet::from([0, 2, 4, 7]),
+        };
+        assert_eq!(valid.validate(&params), Ok(()));
+
+        let mut duplicate = valid.clone();
+        duplicate.bucket_mapping[0] = 2;
+        assert_eq!(
+            duplicate.validate(&params),
+            Err(Error::DuplicateBucketMapping { index: 2 })
+        );
+
+        let mut short = valid.clone();
+        short.bucket_mapping.pop();
+        assert!(short.validate(&params).is_err());
+
+        let mut out_of_range = valid;
+        out_of_range.openings = BTreeSet::from([0, 2, 4, 8]);
+        assert_eq!(
+            out_of_range.validate(&params),
+            Err(Error::OpeningOutOfRange { index: 8, M: 8 })
+        );
+    }
+}
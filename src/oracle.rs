@@ -1,46 +1,630 @@
-use crate::G;
-use curve25519_dalek::{ristretto::RistrettoPoint as Point, scalar::Scalar};
-use rand::{CryptoRng, RngCore};
+use crate::{
+    common::{digit_message, n_digits, schnorr_challenge, to_digits, AttestationScheme, Params},
+    error::{Error, Result},
+    schnorr::{self, Signature},
+    G,
+};
+use curve25519_dalek::{
+    ristretto::RistrettoPoint as Point, scalar::Scalar, traits::VartimeMultiscalarMul,
+};
+use rand::{CryptoRng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{digest::Digest, Sha256, Sha512};
+use std::collections::BTreeMap;
 
-#[allow(dead_code)]
+/// An oracle with a static key and nonces for each event all derived from a single seed.
+///
+/// The oracle keeps a record of the events it has announced and attested to so that it never
+/// uses an event's nonces for two different announcements or outcomes (which would reveal its
+/// key). The record must be persisted (e.g. with [`codec::encrypt`](crate::codec::encrypt)) and
+/// restored rather than recreating the oracle from its seed.
+#[derive(Serialize, Deserialize)]
 pub struct Oracle {
+    seed: [u8; 32],
     sk: Scalar,
     pk: Point,
-    nsk: Vec<Scalar>,
-    npk: Vec<Point>,
+    announced: BTreeMap<String, OracleAnnouncement>,
+    attested: BTreeMap<String, u32>,
 }
 
 impl Oracle {
-    pub fn random(n_outcomes: u32, rng: &mut (impl RngCore + CryptoRng)) -> Self {
-        let n_nonces = (n_outcomes as f32).log2().ceil() as usize;
-        Self::new(
-            Scalar::random(rng),
-            (0..n_nonces).map(|_| Scalar::random(rng)).collect(),
-        )
+    pub fn random(rng: &mut (impl RngCore + CryptoRng)) -> Self {
+        let mut seed = [0u8; 32];
+        rng.fill_bytes(&mut seed);
+        Self::from_seed(seed)
     }
 
-    pub fn new(sk: Scalar, nsk: Vec<Scalar>) -> Self {
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        let sk = Scalar::from_hash(
+            Sha512::default()
+                .chain(b"dlc-venc-adaptor/oracle-key")
+                .chain(seed),
+        );
         let pk = &sk * &*G;
-        let npk = nsk.iter().map(|nsk| nsk * &*G).collect();
-        Self { sk, pk, nsk, npk }
+        Self {
+            seed,
+            sk,
+            pk,
+            announced: BTreeMap::new(),
+            attested: BTreeMap::new(),
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn seed(&self) -> [u8; 32] {
+        self.seed
     }
 
     pub fn public_key(&self) -> Point {
         self.pk
     }
 
-    pub fn public_nonce(&self) -> &[Point] {
-        &self.npk
+    /// The secret nonce for a digit of an event. It only depends on the seed and the event so the
+    /// oracle doesn't need to remember it. The outcome descriptor and maturity are part of the
+    /// event so an oracle that lost its state and announces an event id again with either changed
+    /// doesn't reuse the nonces.
+    fn event_nonce(
+        &self,
+        event_id: &str,
+        outcome_descriptor: &OutcomeDescriptor,
+        maturity: u64,
+        digit_index: usize,
+    ) -> Scalar {
+        let encoded_descriptor =
+            bincode::serde::encode_to_vec(outcome_descriptor, bincode::config::standard())
+                .expect("outcome descriptors can always be encoded");
+        Scalar::from_hash(
+            Sha512::default()
+                .chain(b"dlc-venc-adaptor/oracle-nonce")
+                .chain(self.seed)
+                .chain((event_id.len() as u64).to_be_bytes())
+                .chain(event_id)
+                .chain(encoded_descriptor)
+                .chain(maturity.to_be_bytes())
+                .chain((digit_index as u64).to_be_bytes()),
+        )
     }
 
-    pub fn attest(&self, outcome_index: u32) -> Vec<Scalar> {
-        (0..self.nsk.len())
-            .map(|bit_index| {
-                let bit_value = ((outcome_index >> bit_index) & 0x01) == 1;
-                let nsk = &self.nsk[bit_index];
-                let nonce_coef = Scalar::from(bit_value as u32 + 1);
-                nonce_coef * nsk + self.sk
+    /// Commits to the event's nonces and how it will attest to the outcome by signing them with
+    /// the oracle's key.
+    ///
+    /// Announcing an event again returns the original announcement but announcing it with a
+    /// different outcome descriptor or maturity is an error since it would reuse the nonces.
+    pub fn announce(
+        &mut self,
+        event_id: String,
+        outcome_descriptor: OutcomeDescriptor,
+        maturity: u64,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Result<OracleAnnouncement> {
+        if let Some(announcement) = self.announced.get(&event_id) {
+            if announcement.event.outcome_descriptor != outcome_descriptor
+                || announcement.event.maturity != maturity
+            {
+                return Err(Error::EventAlreadyAnnounced { event_id });
+            }
+            return Ok(announcement.clone());
+        }
+        if outcome_descriptor.n_outcomes == 0 {
+            return Err(Error::NoOutcomes);
+        }
+        if outcome_descriptor.base < 2 {
+            return Err(Error::InvalidBase(outcome_descriptor.base));
+        }
+
+        let n_nonces = n_digits(outcome_descriptor.n_outcomes, outcome_descriptor.base) as usize;
+        let nonces = (0..n_nonces)
+            .map(|digit_index| {
+                &self.event_nonce(&event_id, &outcome_descriptor, maturity, digit_index) * &*G
+            })
+            .collect();
+        let event = OracleEvent {
+            event_id,
+            outcome_descriptor,
+            maturity,
+            nonces,
+        };
+        let signature = schnorr::sign(&self.sk, &event.signing_message(), rng);
+        let announcement = OracleAnnouncement {
+            public_key: self.pk,
+            event,
+            signature,
+        };
+        self.announced
+            .insert(announcement.event.event_id.clone(), announcement.clone());
+        Ok(announcement)
+    }
+
+    /// Attests to each base `base` digit of the outcome with the nonce for that digit.
+    ///
+    /// The event must have been announced and can only be attested to one outcome (attesting to
+    /// it again just gives the same attestation).
+    pub fn attest(&mut self, event_id: &str, outcome_index: u32) -> Result<OracleAttestation> {
+        let event = &self
+            .announced
+            .get(event_id)
+            .ok_or_else(|| Error::UnknownEvent {
+                event_id: event_id.into(),
+            })?
+            .event;
+        let (outcome_descriptor, maturity) = (event.outcome_descriptor, event.maturity);
+        if outcome_index >= outcome_descriptor.n_outcomes {
+            return Err(Error::OutcomeOutOfRange {
+                outcome_index,
+                n_outcomes: outcome_descriptor.n_outcomes,
+            });
+        }
+        if let Some(&attested) = self
+            .attested
+            .get(event_id)
+            .filter(|attested| **attested != outcome_index)
+        {
+            return Err(Error::AlreadyAttested {
+                event_id: event_id.into(),
+                outcome_index: attested,
+            });
+        }
+        self.attested.insert(event_id.into(), outcome_index);
+
+        let n_digits = n_digits(outcome_descriptor.n_outcomes, outcome_descriptor.base) as usize;
+        let digit_attestations = to_digits(outcome_index, outcome_descriptor.base, n_digits)
+            .into_iter()
+            .enumerate()
+            .map(|(digit_index, digit_value)| {
+                let nsk = self.event_nonce(event_id, &outcome_descriptor, maturity, digit_index);
+                match outcome_descriptor.attestation_scheme {
+                    AttestationScheme::Linear => {
+                        let nonce_coef = Scalar::from(digit_value + 1);
+                        nonce_coef * nsk + self.sk
+                    }
+                    AttestationScheme::Schnorr => {
+                        let npk = &nsk * &*G;
+                        let challenge =
+                            schnorr_challenge(&npk, &self.pk, &digit_message(digit_value));
+                        nsk + challenge * self.sk
+                    }
+                }
             })
-            .collect()
+            .collect();
+        Ok(OracleAttestation {
+            event_id: event_id.into(),
+            outcome_index,
+            digit_attestations,
+        })
+    }
+}
+
+/// What the oracle will attest to and how.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutcomeDescriptor {
+    pub n_outcomes: u32,
+    /// The base of the digits of the outcome index. There is a nonce for each digit.
+    pub base: u32,
+    pub attestation_scheme: AttestationScheme,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OracleEvent {
+    pub event_id: String,
+    pub outcome_descriptor: OutcomeDescriptor,
+    /// When the oracle will attest (seconds since the unix epoch).
+    pub maturity: u64,
+    pub nonces: Vec<Point>,
+}
+
+impl OracleEvent {
+    /// The message the oracle signs to announce the event.
+    pub fn signing_message(&self) -> [u8; 32] {
+        let encoded = bincode::serde::encode_to_vec(self, bincode::config::standard())
+            .expect("events can always be encoded");
+        Sha256::default()
+            .chain(b"dlc-venc-adaptor/announcement")
+            .chain(encoded)
+            .finalize()
+            .into()
+    }
+}
+
+/// An event signed by the oracle so that the nonces are known to belong to the oracle's key and
+/// to that particular event.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OracleAnnouncement {
+    pub public_key: Point,
+    pub event: OracleEvent,
+    pub signature: Signature,
+}
+
+impl OracleAnnouncement {
+    pub fn verify(&self) -> bool {
+        schnorr::verify(
+            &self.public_key,
+            &self.event.signing_message(),
+            &self.signature,
+        )
+    }
+}
+
+/// The oracle's attestation to the outcome of an announced event. There is one scalar for each
+/// digit of the outcome index.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OracleAttestation {
+    pub event_id: String,
+    pub outcome_index: u32,
+    pub digit_attestations: Vec<Scalar>,
+}
+
+impl OracleAttestation {
+    /// Checks the attestation is from the oracle at `oracle_index` in `params`, is for the event in
+    /// `params` and is for `outcome_index`.
+    pub fn verify(&self, oracle_index: usize, params: &Params) -> Result<()> {
+        params.validate()?;
+        self.check_shape(oracle_index, params)?;
+        for (digit_index, (digit_value, digit_attestation)) in self
+            .outcome_digits(params)
+            .into_iter()
+            .zip(&self.digit_attestations)
+            .enumerate()
+        {
+            if digit_attestation * &*G
+                != params.anticipate_at_index(oracle_index, digit_index as u32, digit_value)
+            {
+                return Err(Error::BadAttestation {
+                    oracle_index,
+                    digit_index,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn check_shape(&self, oracle_index: usize, params: &Params) -> Result<()> {
+        if self.event_id != params.event_id {
+            return Err(Error::WrongEvent {
+                expected: params.event_id.clone(),
+                got: self.event_id.clone(),
+            });
+        }
+        if self.outcome_index >= params.n_outcomes {
+            return Err(Error::OutcomeOutOfRange {
+                outcome_index: self.outcome_index,
+                n_outcomes: params.n_outcomes,
+            });
+        }
+        let n_digits = params.n_digits() as usize;
+        if self.digit_attestations.len() != n_digits {
+            return Err(Error::WrongAttestationLength {
+                oracle_index,
+                expected: n_digits,
+                got: self.digit_attestations.len(),
+            });
+        }
+        Ok(())
+    }
+
+    fn outcome_digits(&self, params: &Params) -> Vec<u32> {
+        to_digits(
+            self.outcome_index,
+            params.digit_base(),
+            params.n_digits() as usize,
+        )
+    }
+}
+
+/// Checks an attestation from every oracle in `params` at once by checking a random linear
+/// combination of them. This is much faster than checking each one with
+/// [`OracleAttestation::verify`] but if it fails each one is checked to find out which was bad.
+pub fn verify_attestations(attestations: &[OracleAttestation], params: &Params) -> Result<()> {
+    params.validate()?;
+    if attestations.len() != params.oracle_keys.len() {
+        return Err(Error::WrongNumberOfAttestations {
+            expected: params.oracle_keys.len(),
+            got: attestations.len(),
+        });
+    }
+    for (oracle_index, attestation) in attestations.iter().enumerate() {
+        attestation.check_shape(oracle_index, params)?;
+    }
+
+    // the coefficients only need to be unpredictable to whoever made the attestations
+    let encoded = bincode::serde::encode_to_vec(attestations, bincode::config::standard())
+        .expect("attestations can always be encoded");
+    let seed = Sha256::default()
+        .chain(b"dlc-venc-adaptor/batch-attestations")
+        .chain(params.hash())
+        .chain(encoded)
+        .finalize();
+    let mut rng = ChaCha20Rng::from_seed(seed.into());
+
+    let mut attestation_sum = Scalar::zero();
+    let mut coefficients = vec![];
+    let mut anticipations = vec![];
+    for (oracle_index, attestation) in attestations.iter().enumerate() {
+        for (digit_index, (digit_value, digit_attestation)) in attestation
+            .outcome_digits(params)
+            .into_iter()
+            .zip(&attestation.digit_attestations)
+            .enumerate()
+        {
+            let coefficient = Scalar::random(&mut rng);
+            attestation_sum += coefficient * digit_attestation;
+            coefficients.push(coefficient);
+            anticipations.push(params.anticipate_at_index(
+                oracle_index,
+                digit_index as u32,
+                digit_value,
+            ));
+        }
+    }
+
+    if Point::vartime_multiscalar_mul(&coefficients, &anticipations) == &attestation_sum * &*G {
+        return Ok(());
+    }
+
+    for (oracle_index, attestation) in attestations.iter().enumerate() {
+        attestation.verify(oracle_index, params)?;
+    }
+    unreachable!("the batch can only fail if one of the attestations is bad")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        common::{OutcomeEncoding, Params},
+        error::Error,
+        test_utils::{announce, setup_with, EVENT_ID},
+    };
+
+    const DESCRIPTOR: OutcomeDescriptor = OutcomeDescriptor {
+        n_outcomes: 100,
+        base: 10,
+        attestation_scheme: AttestationScheme::Linear,
+    };
+
+    #[test]
+    fn keys_and_nonces_are_derived_from_the_seed() {
+        let mut oracle = Oracle::from_seed([7u8; 32]);
+        let mut restarted = Oracle::from_seed([7u8; 32]);
+        assert_eq!(oracle.public_key(), restarted.public_key());
+
+        let announcement = oracle
+            .announce("btc-usd".into(), DESCRIPTOR, 0, &mut rand::thread_rng())
+            .unwrap();
+        let other = oracle
+            .announce("eth-usd".into(), DESCRIPTOR, 0, &mut rand::thread_rng())
+            .unwrap();
+        assert_eq!(
+            restarted
+                .announce("btc-usd".into(), DESCRIPTOR, 0, &mut rand::thread_rng())
+                .unwrap()
+                .event
+                .nonces,
+            announcement.event.nonces
+        );
+        assert_ne!(announcement.event.nonces, other.event.nonces);
+        // an oracle that lost its state gets fresh nonces if the event changed
+        let later = Oracle::from_seed([7u8; 32])
+            .announce("btc-usd".into(), DESCRIPTOR, 1, &mut rand::thread_rng())
+            .unwrap();
+        assert_ne!(announcement.event.nonces, later.event.nonces);
+        let schnorr = Oracle::from_seed([7u8; 32])
+            .announce(
+                "btc-usd".into(),
+                OutcomeDescriptor {
+                    attestation_scheme: AttestationScheme::Schnorr,
+                    ..DESCRIPTOR
+                },
+                0,
+                &mut rand::thread_rng(),
+            )
+            .unwrap();
+        assert_ne!(announcement.event.nonces, schnorr.event.nonces);
+
+        // the same event can't be announced with a different outcome descriptor
+        let binary = OutcomeDescriptor {
+            base: 2,
+            ..DESCRIPTOR
+        };
+        assert_eq!(
+            oracle.announce("btc-usd".into(), binary, 0, &mut rand::thread_rng()),
+            Err(Error::EventAlreadyAnnounced {
+                event_id: "btc-usd".into()
+            })
+        );
+
+        let attestation = oracle.attest("btc-usd", 42).unwrap();
+        assert_eq!(oracle.attest("btc-usd", 42), Ok(attestation));
+        assert_eq!(
+            oracle.attest("btc-usd", 43),
+            Err(Error::AlreadyAttested {
+                event_id: "btc-usd".into(),
+                outcome_index: 42
+            })
+        );
+        assert_eq!(
+            oracle.attest("doge-usd", 1),
+            Err(Error::UnknownEvent {
+                event_id: "doge-usd".into()
+            })
+        );
+    }
+
+    #[test]
+    fn params_from_announcements() {
+        let (_, mut announcements) = announce(DESCRIPTOR, 3);
+        let params =
+            Params::from_announcements(&announcements, 2, 20, OutcomeEncoding::Digits, false, None)
+                .unwrap();
+        assert_eq!(params.n_digits(), 2);
+        assert_eq!(params.oracle_keys[1].1, announcements[1].event.nonces);
+        // a direct encoding needs a single nonce
+        assert_eq!(
+            Params::from_announcements(&announcements, 2, 20, OutcomeEncoding::Direct, false, None)
+                .unwrap_err(),
+            Error::WrongNumberOfNonces {
+                oracle_index: 0,
+                expected: 1,
+                got: 2
+            }
+        );
+
+        // the same oracle twice would get two shares
+        let repeated = [
+            announcements[0].clone(),
+            announcements[0].clone(),
+            announcements[1].clone(),
+        ];
+        assert_eq!(
+            Params::from_announcements(&repeated, 2, 20, OutcomeEncoding::Digits, false, None)
+                .unwrap_err(),
+            Error::DuplicateOracle { oracle_index: 1 }
+        );
+        let mut repeated_key = params.clone();
+        repeated_key.oracle_keys[2] = repeated_key.oracle_keys[0].clone();
+        assert_eq!(
+            repeated_key.validate(),
+            Err(Error::DuplicateOracle { oracle_index: 2 })
+        );
+
+        announcements[1].event.maturity += 1;
+        assert_eq!(
+            Params::from_announcements(&announcements, 2, 20, OutcomeEncoding::Digits, false, None)
+                .unwrap_err(),
+            Error::BadAnnouncementSignature { oracle_index: 1 }
+        );
+
+        let binary = OutcomeDescriptor {
+            base: 2,
+            ..DESCRIPTOR
+        };
+        announcements[1] = Oracle::random(&mut rand::thread_rng())
+            .announce(EVENT_ID.into(), binary, 0, &mut rand::thread_rng())
+            .unwrap();
+        assert_eq!(
+            Params::from_announcements(&announcements, 2, 20, OutcomeEncoding::Digits, false, None)
+                .unwrap_err(),
+            Error::MismatchedAnnouncement { oracle_index: 1 }
+        );
+        announcements[1] = Oracle::random(&mut rand::thread_rng())
+            .announce("other".into(), DESCRIPTOR, 0, &mut rand::thread_rng())
+            .unwrap();
+        assert_eq!(
+            Params::from_announcements(&announcements, 2, 20, OutcomeEncoding::Digits, false, None)
+                .unwrap_err(),
+            Error::MismatchedAnnouncement { oracle_index: 1 }
+        );
+
+        for threshold in [0, 4] {
+            assert_eq!(
+                Params::from_announcements(
+                    &announcements,
+                    threshold,
+                    20,
+                    OutcomeEncoding::Digits,
+                    false,
+                    None
+                )
+                .unwrap_err(),
+                Error::InvalidThreshold {
+                    threshold,
+                    n_oracles: 3
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn unusable_descriptors_are_rejected() {
+        // the oracle refuses to announce these so sign them directly
+        let oracle = Oracle::random(&mut rand::thread_rng());
+        for (n_outcomes, base, error) in [(0, 2, Error::NoOutcomes), (3, 0, Error::InvalidBase(0))]
+        {
+            let event = OracleEvent {
+                event_id: EVENT_ID.into(),
+                outcome_descriptor: OutcomeDescriptor {
+                    n_outcomes,
+                    base,
+                    ..DESCRIPTOR
+                },
+                maturity: 0,
+                nonces: vec![],
+            };
+            let announcement = OracleAnnouncement {
+                public_key: oracle.public_key(),
+                signature: schnorr::sign(
+                    &oracle.sk,
+                    &event.signing_message(),
+                    &mut rand::thread_rng(),
+                ),
+                event,
+            };
+            assert_eq!(
+                Params::from_announcements(
+                    &[announcement],
+                    1,
+                    20,
+                    OutcomeEncoding::Digits,
+                    false,
+                    None
+                )
+                .unwrap_err(),
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn batch_verify_attestations() {
+        let schnorr = OutcomeDescriptor {
+            attestation_scheme: AttestationScheme::Schnorr,
+            ..DESCRIPTOR
+        };
+        let (mut oracles, params) = setup_with(schnorr, 3, 2);
+
+        // oracles don't have to agree for their attestations to be valid
+        let mut attestations = oracles
+            .iter_mut()
+            .zip([43, 42, 42])
+            .map(|(oracle, outcome_index)| oracle.attest(EVENT_ID, outcome_index).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(verify_attestations(&attestations, &params), Ok(()));
+
+        // hand built params missing a nonce are rejected rather than panicking
+        let mut missing_nonce = params.clone();
+        missing_nonce.oracle_keys[2].1.pop();
+        let too_few_nonces = Error::WrongNumberOfNonces {
+            oracle_index: 2,
+            expected: 2,
+            got: 1,
+        };
+        assert_eq!(
+            attestations[2].verify(2, &missing_nonce),
+            Err(too_few_nonces.clone())
+        );
+        assert_eq!(
+            verify_attestations(&attestations, &missing_nonce),
+            Err(too_few_nonces)
+        );
+
+        attestations[2].outcome_index = 52;
+        assert_eq!(
+            verify_attestations(&attestations, &params),
+            Err(Error::BadAttestation {
+                oracle_index: 2,
+                digit_index: 1
+            })
+        );
+
+        attestations[1].event_id = "other".into();
+        let wrong_event = Error::WrongEvent {
+            expected: EVENT_ID.into(),
+            got: "other".into(),
+        };
+        assert_eq!(attestations[1].verify(1, &params), Err(wrong_event.clone()));
+        assert_eq!(
+            verify_attestations(&attestations, &params),
+            Err(wrong_event)
+        );
     }
 }
@@ -3,7 +3,7 @@ use curve25519_dalek::{
     ristretto::RistrettoPoint as Point, scalar::Scalar, traits::VartimeMultiscalarMul,
 };
 use rand::{CryptoRng, RngCore};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::iter;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -44,7 +44,7 @@ impl ScalarPoly {
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PointPoly(Vec<Point>);
 
 impl PointPoly {
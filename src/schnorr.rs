@@ -0,0 +1,28 @@
+//! Schnorr signatures over [`G`] with the same challenge as the
+//! [`Schnorr`](crate::common::AttestationScheme::Schnorr) attestation scheme.
+use crate::{common::schnorr_challenge, G};
+use curve25519_dalek::{ristretto::RistrettoPoint as Point, scalar::Scalar};
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Signature {
+    pub R: Point,
+    pub s: Scalar,
+}
+
+pub fn sign(sk: &Scalar, message: &[u8], rng: &mut (impl RngCore + CryptoRng)) -> Signature {
+    let r = Scalar::random(rng);
+    let R = &r * &*G;
+    let pk = sk * &*G;
+    let challenge = schnorr_challenge(&R, &pk, message);
+    Signature {
+        R,
+        s: r + challenge * sk,
+    }
+}
+
+pub fn verify(pk: &Point, message: &[u8], signature: &Signature) -> bool {
+    let challenge = schnorr_challenge(&signature.R, pk, message);
+    &signature.s * &*G == signature.R + challenge * pk
+}
@@ -0,0 +1,300 @@
+//! Sans-IO sessions that drive each party through the protocol.
+//!
+//! A session takes the encoded messages it receives from the transport and returns the encoded
+//! messages it wants sent so it can be used with any transport. It keeps the [`Params`] and
+//! moves through explicit states. Any invalid message from the counterparty aborts the session.
+use crate::{
+    alice::Alice1,
+    bob::{Bob1, Bob2, Recovery},
+    codec::{self, WireMessage},
+    common::Params,
+    error::{Error, Result},
+    messages::*,
+    oracle::OracleAttestation,
+};
+use curve25519_dalek::{ristretto::RistrettoPoint as Point, scalar::Scalar};
+use rand::{CryptoRng, RngCore};
+use std::collections::BTreeMap;
+
+/// An encoded message for the transport to deliver to the counterparty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutgoingMessage {
+    pub bytes: Vec<u8>,
+}
+
+impl OutgoingMessage {
+    fn new(message: &impl WireMessage) -> OutgoingMessage {
+        OutgoingMessage {
+            bytes: codec::encode(message),
+        }
+    }
+}
+
+pub enum AliceState {
+    /// Waiting for Bob's challenge to our `Message1`.
+    AwaitingMessage2(Alice1),
+    /// We've sent `Message3` and have nothing more to do.
+    Done,
+    Aborted(Error),
+}
+
+pub struct AliceSession {
+    params: Params,
+    secret_sigs: Vec<Scalar>,
+    state: AliceState,
+}
+
+impl AliceSession {
+    /// Starts the session returning the messages to send to Bob. With `non_interactive` that's
+    /// `Message1` and `Message3` together and the session is done straight away.
+    pub fn new(
+        params: Params,
+        secret_sigs: Vec<Scalar>,
+        non_interactive: bool,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Result<(AliceSession, Vec<OutgoingMessage>)> {
+        params.validate()?;
+        let (alice, message1) = Alice1::new(&params, rng);
+        let mut outgoing = vec![OutgoingMessage::new(&message1)];
+        let state = if non_interactive {
+            let message3 = alice.prove_non_interactive(secret_sigs.clone(), &params, rng)?;
+            outgoing.push(OutgoingMessage::new(&message3));
+            AliceState::Done
+        } else {
+            AliceState::AwaitingMessage2(alice)
+        };
+        Ok((
+            AliceSession {
+                params,
+                secret_sigs,
+                state,
+            },
+            outgoing,
+        ))
+    }
+
+    pub fn state(&self) -> &AliceState {
+        &self.state
+    }
+
+    pub fn handle_message(
+        &mut self,
+        bytes: &[u8],
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Result<Vec<OutgoingMessage>> {
+        match std::mem::replace(&mut self.state, AliceState::Done) {
+            AliceState::AwaitingMessage2(alice) => {
+                let message3 = codec::decode::<Message2>(bytes).and_then(|message2| {
+                    alice.receive_message(message2, self.secret_sigs.clone(), &self.params, rng)
+                });
+                match message3 {
+                    Ok(message3) => Ok(vec![OutgoingMessage::new(&message3)]),
+                    Err(e) => {
+                        self.state = AliceState::Aborted(e.clone());
+                        Err(e)
+                    }
+                }
+            }
+            state => {
+                self.state = state;
+                Err(Error::SessionClosed)
+            }
+        }
+    }
+}
+
+pub enum BobState {
+    AwaitingMessage1,
+    AwaitingMessage3(Bob1),
+    /// Alice's encryptions have been verified and we're waiting for enough of the oracles to
+    /// attest.
+    AwaitingAttestations {
+        bob: Bob2,
+        /// The attestations received so far by oracle index.
+        attestations: BTreeMap<usize, OracleAttestation>,
+    },
+    Recovered(Recovery),
+    Aborted(Error),
+}
+
+pub struct BobSession {
+    params: Params,
+    outcome_images: Vec<Point>,
+    non_interactive: bool,
+    state: BobState,
+}
+
+impl BobSession {
+    pub fn new(params: Params, outcome_images: Vec<Point>, non_interactive: bool) -> BobSession {
+        BobSession {
+            params,
+            outcome_images,
+            non_interactive,
+            state: BobState::AwaitingMessage1,
+        }
+    }
+
+    pub fn state(&self) -> &BobState {
+        &self.state
+    }
+
+    /// Handles a message from Alice or, once her encryptions are verified, an
+    /// [`OracleAttestation`]. Attestations come from the oracles rather than Alice so one that
+    /// isn't from any of the oracles is rejected without aborting the session.
+    pub fn handle_message(
+        &mut self,
+        bytes: &[u8],
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Result<Vec<OutgoingMessage>> {
+        let params = &self.params;
+        let (state, result) = match std::mem::replace(&mut self.state, BobState::AwaitingMessage1) {
+            BobState::AwaitingMessage1 => {
+                let bob = codec::decode::<Message1>(bytes).and_then(|message1| {
+                    if self.non_interactive {
+                        Ok((Bob1::new_non_interactive(message1, params)?, vec![]))
+                    } else {
+                        let (bob, message2) = Bob1::new(message1, params, rng)?;
+                        Ok((bob, vec![OutgoingMessage::new(&message2)]))
+                    }
+                });
+                match bob {
+                    Ok((bob, outgoing)) => (BobState::AwaitingMessage3(bob), Ok(outgoing)),
+                    Err(e) => (BobState::Aborted(e.clone()), Err(e)),
+                }
+            }
+            BobState::AwaitingMessage3(bob) => {
+                let bob = codec::decode::<Message3>(bytes).and_then(|message3| {
+                    bob.receive_message(message3, self.outcome_images.clone(), params)
+                });
+                match bob {
+                    Ok(bob) => (
+                        BobState::AwaitingAttestations {
+                            bob,
+                            attestations: BTreeMap::new(),
+                        },
+                        Ok(vec![]),
+                    ),
+                    Err(e) => (BobState::Aborted(e.clone()), Err(e)),
+                }
+            }
+            BobState::AwaitingAttestations {
+                bob,
+                mut attestations,
+            } => match Self::add_attestation(bytes, &mut attestations, params) {
+                Err(e) => (BobState::AwaitingAttestations { bob, attestations }, Err(e)),
+                Ok(outcome_index) => {
+                    let attested = attestations
+                        .iter()
+                        .filter(|(_, attestation)| attestation.outcome_index == outcome_index)
+                        .map(|(oracle_index, attestation)| (*oracle_index, attestation))
+                        .collect::<Vec<_>>();
+                    if attested.len() < params.threshold as usize {
+                        (
+                            BobState::AwaitingAttestations { bob, attestations },
+                            Ok(vec![]),
+                        )
+                    } else {
+                        match bob.recover(outcome_index, &attested, params) {
+                            Ok(recovery) => (BobState::Recovered(recovery), Ok(vec![])),
+                            // Alice corrupted some of the shares so wait for more oracles
+                            Err(Error::InsufficientShares { .. }) => (
+                                BobState::AwaitingAttestations { bob, attestations },
+                                Ok(vec![]),
+                            ),
+                            Err(e) => (BobState::Aborted(e.clone()), Err(e)),
+                        }
+                    }
+                }
+            },
+            state => (state, Err(Error::SessionClosed)),
+        };
+        self.state = state;
+        result
+    }
+
+    /// Finds the oracle the attestation is from and adds it returning the attested outcome.
+    /// Attestations to other events are rejected even if they are from one of the oracles.
+    fn add_attestation(
+        bytes: &[u8],
+        attestations: &mut BTreeMap<usize, OracleAttestation>,
+        params: &Params,
+    ) -> Result<u32> {
+        let attestation = codec::decode::<OracleAttestation>(bytes)?;
+        if attestation.event_id != params.event_id {
+            return Err(Error::WrongEvent {
+                expected: params.event_id.clone(),
+                got: attestation.event_id,
+            });
+        }
+        let oracle_index = (0..params.oracle_keys.len())
+            .find(|oracle_index| attestation.verify(*oracle_index, params).is_ok())
+            .ok_or(Error::UnknownOracle)?;
+        let outcome_index = attestation.outcome_index;
+        attestations.entry(oracle_index).or_insert(attestation);
+        Ok(outcome_index)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::{gen_secrets, setup, EVENT_ID};
+
+    #[test]
+    fn sessions_run_the_protocol() {
+        let (mut oracles, params) = setup(8, 2, 3, 2);
+        let (secret_sigs, outcome_images) = gen_secrets(&params);
+
+        let (mut alice, m1) = AliceSession::new(
+            params.clone(),
+            secret_sigs.clone(),
+            false,
+            &mut rand::thread_rng(),
+        )
+        .unwrap();
+        let mut bob = BobSession::new(params, outcome_images, false);
+        let m2 = bob
+            .handle_message(&m1[0].bytes, &mut rand::thread_rng())
+            .unwrap();
+        // Bob isn't expecting another Message1
+        let mut other_bob = BobSession::new(bob.params.clone(), vec![], false);
+        other_bob
+            .handle_message(&m1[0].bytes, &mut rand::thread_rng())
+            .unwrap();
+        assert!(other_bob
+            .handle_message(&m1[0].bytes, &mut rand::thread_rng())
+            .is_err());
+        assert!(matches!(other_bob.state(), BobState::Aborted(_)));
+
+        let m3 = alice
+            .handle_message(&m2[0].bytes, &mut rand::thread_rng())
+            .unwrap();
+        assert!(matches!(alice.state(), AliceState::Done));
+        bob.handle_message(&m3[0].bytes, &mut rand::thread_rng())
+            .unwrap();
+
+        let attestation = oracles[2].attest(EVENT_ID, 6).unwrap();
+        let mut forged = attestation.clone();
+        forged.outcome_index = 5;
+        assert_eq!(
+            bob.handle_message(&codec::encode(&forged), &mut rand::thread_rng()),
+            Err(Error::UnknownOracle)
+        );
+        let mut other_event = attestation.clone();
+        other_event.event_id = "other".into();
+        assert!(matches!(
+            bob.handle_message(&codec::encode(&other_event), &mut rand::thread_rng()),
+            Err(Error::WrongEvent { .. })
+        ));
+        bob.handle_message(&codec::encode(&attestation), &mut rand::thread_rng())
+            .unwrap();
+        assert!(matches!(bob.state(), BobState::AwaitingAttestations { .. }));
+        let attestation = oracles[0].attest(EVENT_ID, 6).unwrap();
+        bob.handle_message(&codec::encode(&attestation), &mut rand::thread_rng())
+            .unwrap();
+        match bob.state() {
+            BobState::Recovered(recovery) => assert_eq!(recovery.secret, secret_sigs[6]),
+            _ => panic!("Bob should have recovered the secret"),
+        }
+    }
+}
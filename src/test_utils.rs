@@ -0,0 +1,112 @@
+//! Fixtures shared by the tests of several modules.
+use crate::{
+    alice::Alice1,
+    bob::{Bob1, Bob2},
+    common::{AttestationScheme, OutcomeEncoding, Params},
+    oracle::{Oracle, OracleAnnouncement, OutcomeDescriptor},
+    G,
+};
+use curve25519_dalek::{ristretto::RistrettoPoint as Point, scalar::Scalar};
+
+/// The event every fixture announces.
+pub const EVENT_ID: &str = "event";
+
+/// Random oracles that have each announced [`EVENT_ID`] with `outcome_descriptor`.
+pub fn announce(
+    outcome_descriptor: OutcomeDescriptor,
+    n_oracles: usize,
+) -> (Vec<Oracle>, Vec<OracleAnnouncement>) {
+    let mut oracles = (0..n_oracles)
+        .map(|_| Oracle::random(&mut rand::thread_rng()))
+        .collect::<Vec<_>>();
+    let announcements = oracles
+        .iter_mut()
+        .map(|oracle| {
+            oracle
+                .announce(
+                    EVENT_ID.into(),
+                    outcome_descriptor,
+                    0,
+                    &mut rand::thread_rng(),
+                )
+                .unwrap()
+        })
+        .collect();
+    (oracles, announcements)
+}
+
+/// A copy of `oracle` rebuilt from its seed that has announced [`EVENT_ID`] for `params`. It has
+/// no record of what the original attested to so tests use it to make the oracle attest to
+/// another outcome.
+pub fn rebuild_oracle(oracle: &Oracle, params: &Params) -> Oracle {
+    let mut copy = Oracle::from_seed(oracle.seed());
+    let outcome_descriptor = OutcomeDescriptor {
+        n_outcomes: params.n_outcomes,
+        base: params.base,
+        attestation_scheme: params.attestation_scheme,
+    };
+    copy.announce(
+        EVENT_ID.into(),
+        outcome_descriptor,
+        0,
+        &mut rand::thread_rng(),
+    )
+    .unwrap();
+    copy
+}
+
+/// Params for the oracles' announcements. They are built for the non-interactive protocol so the
+/// tests can use them with either.
+pub fn setup_with(
+    outcome_descriptor: OutcomeDescriptor,
+    n_oracles: usize,
+    threshold: u16,
+) -> (Vec<Oracle>, Params) {
+    let (oracles, announcements) = announce(outcome_descriptor, n_oracles);
+    let params = Params::from_announcements(
+        &announcements,
+        threshold,
+        20,
+        OutcomeEncoding::Digits,
+        true,
+        None,
+    )
+    .unwrap();
+    (oracles, params)
+}
+
+pub fn setup(
+    n_outcomes: u32,
+    base: u32,
+    n_oracles: usize,
+    threshold: u16,
+) -> (Vec<Oracle>, Params) {
+    let outcome_descriptor = OutcomeDescriptor {
+        n_outcomes,
+        base,
+        attestation_scheme: AttestationScheme::Linear,
+    };
+    setup_with(outcome_descriptor, n_oracles, threshold)
+}
+
+/// Alice's secret for each of the params' secrets along with its image.
+pub fn gen_secrets(params: &Params) -> (Vec<Scalar>, Vec<Point>) {
+    let secret_sigs = (0..params.n_secrets())
+        .map(|_| Scalar::random(&mut rand::thread_rng()))
+        .collect::<Vec<_>>();
+    let outcome_images = secret_sigs.iter().map(|s| s * &*G).collect();
+    (secret_sigs, outcome_images)
+}
+
+/// Runs the interactive protocol for random secrets. Returns Bob's state once he has checked
+/// Alice's encryptions along with the secrets.
+pub fn encrypt_secrets(params: &Params) -> (Bob2, Vec<Scalar>) {
+    let (secret_sigs, outcome_images) = gen_secrets(params);
+    let (alice, m1) = Alice1::new(params, &mut rand::thread_rng());
+    let (bob, m2) = Bob1::new(m1, params, &mut rand::thread_rng()).unwrap();
+    let m3 = alice
+        .receive_message(m2, secret_sigs.clone(), params, &mut rand::thread_rng())
+        .unwrap();
+    let bob = bob.receive_message(m3, outcome_images, params).unwrap();
+    (bob, secret_sigs)
+}
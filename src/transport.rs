@@ -0,0 +1,84 @@
+//! Length-prefixed framing of encoded messages over a byte stream.
+//!
+//! Each frame is the length of the encoded message as a big-endian `u32` followed by the message
+//! (see [`codec`](crate::codec::encode)). This is all a stream transport like TCP or a Unix socket needs
+//! to carry the messages [`session`](crate::session) produces.
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+
+/// Frames longer than this are rejected. `Message3` is by far the largest message and is well
+/// under this for any practical params. Frames are read as their bytes arrive so a peer can't
+/// make us allocate much more than it has actually sent.
+pub const MAX_FRAME_LEN: u32 = 1 << 30;
+
+pub fn write_frame(stream: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(bytes.len())
+        .ok()
+        .filter(|len| *len <= MAX_FRAME_LEN)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "frame too long"))?;
+    stream.write_all(&len.to_be_bytes())?;
+    stream.write_all(bytes)?;
+    stream.flush()
+}
+
+/// Reads the next frame. Returns `None` if the stream was closed between frames. Closing it
+/// anywhere else (even within the length prefix) is an error.
+pub fn read_frame(stream: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    loop {
+        match stream.read(&mut len[..1]) {
+            Ok(0) => return Ok(None),
+            Ok(_) => break,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    stream.read_exact(&mut len[1..])?;
+    let len = u32::from_be_bytes(len);
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "frame too long"));
+    }
+    let mut bytes = vec![];
+    stream.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len as usize {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(Some(bytes))
+}
+
+/// What a client asks an oracle server for. The server answers with a frame for each of its
+/// oracles and closes the connection.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OracleRequest {
+    Announcements { event_id: String },
+    Attestations { event_id: String },
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn frames_round_trip() {
+        let mut stream = vec![];
+        write_frame(&mut stream, b"hello").unwrap();
+        write_frame(&mut stream, b"").unwrap();
+        let mut reader = &stream[..];
+        assert_eq!(read_frame(&mut reader).unwrap(), Some(b"hello".to_vec()));
+        assert_eq!(read_frame(&mut reader).unwrap(), Some(vec![]));
+        assert_eq!(read_frame(&mut reader).unwrap(), None);
+
+        // a truncated frame is an error rather than the end of the stream
+        assert!(read_frame(&mut &stream[..7]).is_err());
+        assert!(read_frame(&mut &stream[..2]).is_err());
+        let too_long = (MAX_FRAME_LEN + 1).to_be_bytes();
+        assert!(read_frame(&mut &too_long[..]).is_err());
+        // claiming a long frame without sending it doesn't allocate the whole frame up front
+        let mut short = MAX_FRAME_LEN.to_be_bytes().to_vec();
+        short.extend(b"abc");
+        assert_eq!(
+            read_frame(&mut &short[..]).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+}
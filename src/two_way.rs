@@ -0,0 +1,155 @@
+//! A symmetric session where both parties encrypt their secrets to each other.
+//!
+//! In a DLC each party needs to complete the other's signatures for the outcomes that favour them
+//! so the protocol is run in both directions with the same [`Params`]. The initiator encrypts to
+//! the responder with the interactive cut-and-choose while the responder encrypts to the
+//! initiator non-interactively (see [`Bob1::gen_message2_non_interactive`]) so the whole exchange
+//! is three messages:
+//!
+//! 1. The initiator sends its [`Message1`].
+//! 2. The responder sends a [`TwoWayMessage2`] with its challenge for the initiator's commitments
+//!    along with its own `Message1` and `Message3`.
+//! 3. The initiator checks the responder's proof and answers the challenge with its `Message3`.
+//!
+//! Since the responder can grind on its commitments the params must be built for the
+//! non-interactive protocol.
+use crate::{
+    alice::Alice1,
+    bob::{Bob1, Bob2},
+    common::Params,
+    error::Result,
+    messages::*,
+};
+use curve25519_dalek::{ristretto::RistrettoPoint as Point, scalar::Scalar};
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Initiator1 {
+    alice: Alice1,
+    secret_sigs: Vec<Scalar>,
+    outcome_images: Vec<Point>,
+}
+
+impl Initiator1 {
+    /// `secret_sigs` are our secrets for the responder and `outcome_images` the images of the
+    /// responder's secrets for us.
+    pub fn new(
+        secret_sigs: Vec<Scalar>,
+        outcome_images: Vec<Point>,
+        params: &Params,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> (Initiator1, Message1) {
+        let (alice, message1) = Alice1::new(params, rng);
+        (
+            Initiator1 {
+                alice,
+                secret_sigs,
+                outcome_images,
+            },
+            message1,
+        )
+    }
+
+    /// Verifies the responder's encryptions before answering its challenge so we don't reveal
+    /// anything to a responder that cheated.
+    pub fn receive_message(
+        self,
+        message: TwoWayMessage2,
+        params: &Params,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Result<(Bob2, Message3)> {
+        let bob = Bob1::new_non_interactive(message.message1, params)?.receive_message(
+            message.message3,
+            self.outcome_images,
+            params,
+        )?;
+        let message3 =
+            self.alice
+                .receive_message(message.message2, self.secret_sigs, params, rng)?;
+        Ok((bob, message3))
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Responder1 {
+    bob: Bob1,
+    outcome_images: Vec<Point>,
+}
+
+impl Responder1 {
+    /// `secret_sigs` are our secrets for the initiator and `outcome_images` the images of the
+    /// initiator's secrets for us.
+    pub fn new(
+        message: Message1,
+        secret_sigs: Vec<Scalar>,
+        outcome_images: Vec<Point>,
+        params: &Params,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Result<(Responder1, TwoWayMessage2)> {
+        let (bob, message2) = Bob1::new(message, params, rng)?;
+        let (alice, message1) = Alice1::new(params, rng);
+        let message3 = alice.prove_non_interactive(secret_sigs, params, rng)?;
+        Ok((
+            Responder1 {
+                bob,
+                outcome_images,
+            },
+            TwoWayMessage2 {
+                message2,
+                message1,
+                message3,
+            },
+        ))
+    }
+
+    pub fn receive_message(self, message: Message3, params: &Params) -> Result<Bob2> {
+        self.bob
+            .receive_message(message, self.outcome_images, params)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::{gen_secrets, setup, EVENT_ID};
+
+    #[test]
+    fn both_parties_recover_the_others_secret() {
+        let (mut oracles, params) = setup(8, 2, 2, 2);
+        let (initiator_secrets, initiator_images) = gen_secrets(&params);
+        let (responder_secrets, responder_images) = gen_secrets(&params);
+
+        let (initiator, m1) = Initiator1::new(
+            initiator_secrets.clone(),
+            responder_images,
+            &params,
+            &mut rand::thread_rng(),
+        );
+        let (responder, m2) = Responder1::new(
+            m1,
+            responder_secrets.clone(),
+            initiator_images,
+            &params,
+            &mut rand::thread_rng(),
+        )
+        .unwrap();
+        let (initiator, m3) = initiator
+            .receive_message(m2, &params, &mut rand::thread_rng())
+            .unwrap();
+        let responder = responder.receive_message(m3, &params).unwrap();
+
+        let attestations = oracles
+            .iter_mut()
+            .map(|oracle| oracle.attest(EVENT_ID, 5).unwrap())
+            .collect::<Vec<_>>();
+        let recovery = initiator
+            .receive_oracle_attestation(5, &attestations, &params)
+            .unwrap();
+        assert_eq!(recovery.secret, responder_secrets[5]);
+        let recovery = responder
+            .receive_oracle_attestation(5, &attestations, &params)
+            .unwrap();
+        assert_eq!(recovery.secret, initiator_secrets[5]);
+    }
+}